@@ -0,0 +1,362 @@
+#[cfg(any(
+    feature = "gzip",
+    feature = "deflate",
+    feature = "brotli",
+    feature = "zstd"
+))]
+use std::io::Read;
+#[cfg(any(feature = "gzip", feature = "deflate", feature = "brotli"))]
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use axum::body::{Body, Bytes, HttpBody};
+use axum::http::header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH, VARY};
+use axum::http::{HeaderMap, HeaderValue};
+use axum::response::Response;
+use http_body_util::BodyExt;
+
+use crate::ProtobufRejection;
+#[cfg(feature = "serde")]
+use crate::config::DEFAULT_COMPRESSION_THRESHOLD;
+use crate::media_type::parse_qvalue;
+
+/// Content codings that can be decoded and encoded, depending on the enabled features.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ContentCoding {
+    #[cfg(feature = "gzip")]
+    Gzip,
+    #[cfg(feature = "deflate")]
+    Deflate,
+    #[cfg(feature = "brotli")]
+    Brotli,
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+/// Supported content codings, by order of preference.
+const SUPPORTED_CODINGS: &[ContentCoding] = &[
+    #[cfg(feature = "zstd")]
+    ContentCoding::Zstd,
+    #[cfg(feature = "brotli")]
+    ContentCoding::Brotli,
+    #[cfg(feature = "gzip")]
+    ContentCoding::Gzip,
+    #[cfg(feature = "deflate")]
+    ContentCoding::Deflate,
+];
+
+impl ContentCoding {
+    fn parse(coding: &str) -> Option<Self> {
+        match coding.to_ascii_lowercase().as_str() {
+            #[cfg(feature = "gzip")]
+            "gzip" | "x-gzip" => Some(ContentCoding::Gzip),
+            #[cfg(feature = "deflate")]
+            "deflate" => Some(ContentCoding::Deflate),
+            #[cfg(feature = "brotli")]
+            "br" => Some(ContentCoding::Brotli),
+            #[cfg(feature = "zstd")]
+            "zstd" => Some(ContentCoding::Zstd),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn header_value(self) -> HeaderValue {
+        match self {
+            #[cfg(feature = "gzip")]
+            ContentCoding::Gzip => HeaderValue::from_static("gzip"),
+            #[cfg(feature = "deflate")]
+            ContentCoding::Deflate => HeaderValue::from_static("deflate"),
+            #[cfg(feature = "brotli")]
+            ContentCoding::Brotli => HeaderValue::from_static("br"),
+            #[cfg(feature = "zstd")]
+            ContentCoding::Zstd => HeaderValue::from_static("zstd"),
+        }
+    }
+
+    /// Choose the best supported coding based on the `accept-encoding` header, if any.
+    ///
+    /// The coding with the highest q-value wins; ties are broken using our order of preference.
+    pub(crate) fn negotiate(headers: &HeaderMap) -> Option<Self> {
+        let mut explicit: Vec<(ContentCoding, u16)> = Vec::new();
+        let mut wildcard = None;
+
+        for value in headers.get_all(ACCEPT_ENCODING) {
+            let Ok(value) = value.to_str() else {
+                continue;
+            };
+            for item in value.split(',') {
+                let mut parts = item.split(';').map(str::trim);
+                let coding = parts.next().unwrap_or_default();
+                let q = parts
+                    .filter_map(|param| param.split_once('='))
+                    .find(|(name, _)| name.trim().eq_ignore_ascii_case("q"))
+                    .map_or(Some(1000), |(_, q)| parse_qvalue(q.trim()));
+                // Codings with an invalid q-value are ignored
+                let Some(q) = q else {
+                    continue;
+                };
+
+                if coding == "*" {
+                    wildcard = Some(q);
+                } else if let Some(coding) = ContentCoding::parse(coding) {
+                    explicit.push((coding, q));
+                }
+            }
+        }
+
+        SUPPORTED_CODINGS
+            .iter()
+            .filter_map(|coding| {
+                let q = explicit
+                    .iter()
+                    .find(|(c, _)| c == coding)
+                    .map(|(_, q)| *q)
+                    .or(wildcard)?;
+                (q > 0).then_some((*coding, q))
+            })
+            // `max_by_key` returns the last maximum, so iterate in reverse to favor our preferences
+            .rev()
+            .max_by_key(|(_, q)| *q)
+            .map(|(coding, _)| coding)
+    }
+
+    /// Parse the `content-encoding` header, in the order in which the codings were applied.
+    ///
+    /// Returns an error if a coding is not supported.
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Result<Vec<Self>, ProtobufRejection> {
+        let mut codings = Vec::new();
+        for value in headers.get_all(CONTENT_ENCODING) {
+            let value = value
+                .to_str()
+                .map_err(|_| ProtobufRejection::UnsupportedContentEncoding)?;
+            for coding in value.split(',').map(str::trim) {
+                if coding.is_empty() || coding.eq_ignore_ascii_case("identity") {
+                    continue;
+                }
+                codings.push(
+                    ContentCoding::parse(coding)
+                        .ok_or(ProtobufRejection::UnsupportedContentEncoding)?,
+                );
+            }
+        }
+        Ok(codings)
+    }
+
+    /// Decode some data, stopping as soon as the decoded data is larger than the limit.
+    pub(crate) fn decode(
+        self,
+        input: &[u8],
+        limit: Option<usize>,
+    ) -> Result<Bytes, ProtobufRejection> {
+        #[cfg(any(
+            feature = "gzip",
+            feature = "deflate",
+            feature = "brotli",
+            feature = "zstd"
+        ))]
+        {
+            let decoder: Box<dyn Read + '_> = match self {
+                #[cfg(feature = "gzip")]
+                ContentCoding::Gzip => Box::new(flate2::read::MultiGzDecoder::new(input)),
+                #[cfg(feature = "deflate")]
+                ContentCoding::Deflate => Box::new(flate2::read::ZlibDecoder::new(input)),
+                #[cfg(feature = "brotli")]
+                ContentCoding::Brotli => Box::new(brotli::Decompressor::new(input, 4096)),
+                #[cfg(feature = "zstd")]
+                ContentCoding::Zstd => Box::new(
+                    zstd::Decoder::new(input)
+                        .map_err(|_| ProtobufRejection::FailedToDecompressBody)?,
+                ),
+            };
+
+            // Read one more byte than the limit, to detect that it is exceeded
+            let max_len = limit.map_or(u64::MAX, |limit| limit as u64 + 1);
+            let mut output = Vec::new();
+            decoder
+                .take(max_len)
+                .read_to_end(&mut output)
+                .map_err(|_| ProtobufRejection::FailedToDecompressBody)?;
+
+            if limit.is_some_and(|limit| output.len() > limit) {
+                return Err(ProtobufRejection::PayloadTooLarge);
+            }
+            Ok(output.into())
+        }
+
+        #[cfg(not(any(
+            feature = "gzip",
+            feature = "deflate",
+            feature = "brotli",
+            feature = "zstd"
+        )))]
+        {
+            let _ = (input, limit);
+            match self {}
+        }
+    }
+
+    fn encode(self, input: &[u8]) -> std::io::Result<Vec<u8>> {
+        #[cfg(any(
+            feature = "gzip",
+            feature = "deflate",
+            feature = "brotli",
+            feature = "zstd"
+        ))]
+        {
+            match self {
+                #[cfg(feature = "gzip")]
+                ContentCoding::Gzip => {
+                    let mut encoder =
+                        flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                    encoder.write_all(input)?;
+                    encoder.finish()
+                }
+                #[cfg(feature = "deflate")]
+                ContentCoding::Deflate => {
+                    let mut encoder =
+                        flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+                    encoder.write_all(input)?;
+                    encoder.finish()
+                }
+                #[cfg(feature = "brotli")]
+                ContentCoding::Brotli => {
+                    // A moderate quality, as the highest ones are too slow for dynamic responses
+                    let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, 4, 22);
+                    encoder.write_all(input)?;
+                    encoder.flush()?;
+                    Ok(encoder.into_inner())
+                }
+                #[cfg(feature = "zstd")]
+                ContentCoding::Zstd => zstd::encode_all(input, 0),
+            }
+        }
+
+        #[cfg(not(any(
+            feature = "gzip",
+            feature = "deflate",
+            feature = "brotli",
+            feature = "zstd"
+        )))]
+        {
+            let _ = input;
+            match self {}
+        }
+    }
+}
+
+/// Compress the body of a response with the given coding, if it is at least `threshold` bytes long.
+///
+/// Responses that are already encoded, or whose size is not known in advance (like streams), are left untouched.
+pub(crate) fn compress_response(
+    res: Response,
+    coding: Option<ContentCoding>,
+    threshold: usize,
+) -> Response {
+    compress(res, coding, threshold, &Arc::default())
+}
+
+/// Compress a response on behalf of a handler, according to the `accept-encoding` header.
+///
+/// If the [`ProtobufConfig`](crate::ProtobufConfig) layer is applied, it undoes this compression
+/// (see [`HandlerCompression`]) to follow its own configuration instead.
+#[cfg(feature = "serde")]
+pub(crate) fn compress_handler_response(mut res: Response, header_map: &HeaderMap) -> Response {
+    let uncompressed = Arc::default();
+    res.extensions_mut().insert(HandlerCompression {
+        uncompressed: Arc::clone(&uncompressed),
+    });
+    compress(
+        res,
+        ContentCoding::negotiate(header_map),
+        DEFAULT_COMPRESSION_THRESHOLD,
+        &uncompressed,
+    )
+}
+
+type UncompressedBody = Arc<Mutex<Option<(Body, Option<HeaderValue>)>>>;
+
+/// Added to the extensions of responses compressed by [`compress_handler_response`], so that the
+/// [`ProtobufConfig`](crate::ProtobufConfig) layer can compress them according to its configuration.
+#[derive(Clone)]
+pub(crate) struct HandlerCompression {
+    /// The uncompressed body and its `content-length`, until the compressed body is read.
+    uncompressed: UncompressedBody,
+}
+
+impl HandlerCompression {
+    /// Restore the uncompressed body of a response, along with its headers.
+    pub(crate) fn undo(self, mut res: Response) -> Response {
+        let Some((body, content_length)) = self
+            .uncompressed
+            .lock()
+            .ok()
+            .and_then(|mut uncompressed| uncompressed.take())
+        else {
+            return res;
+        };
+
+        let headers = res.headers_mut();
+        headers.remove(CONTENT_ENCODING);
+        if let Some(content_length) = content_length {
+            headers.insert(CONTENT_LENGTH, content_length);
+        }
+        *res.body_mut() = body;
+        res
+    }
+}
+
+fn compress(
+    mut res: Response,
+    coding: Option<ContentCoding>,
+    threshold: usize,
+    uncompressed: &UncompressedBody,
+) -> Response {
+    if SUPPORTED_CODINGS.is_empty() || res.headers().contains_key(CONTENT_ENCODING) {
+        return res;
+    }
+    if !res
+        .headers()
+        .get_all(VARY)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|name| name.trim().eq_ignore_ascii_case("accept-encoding"))
+    {
+        res.headers_mut()
+            .append(VARY, HeaderValue::from_static("accept-encoding"));
+    }
+
+    let Some(coding) = coding else {
+        return res;
+    };
+    if res
+        .body()
+        .size_hint()
+        .exact()
+        .is_none_or(|len| len < threshold as u64)
+    {
+        return res;
+    }
+
+    res.headers_mut()
+        .insert(CONTENT_ENCODING, coding.header_value());
+    let content_length = res.headers_mut().remove(CONTENT_LENGTH);
+    if let Ok(mut uncompressed) = uncompressed.lock() {
+        *uncompressed = Some((std::mem::take(res.body_mut()), content_length));
+    }
+    let uncompressed = Arc::clone(uncompressed);
+    *res.body_mut() = Body::from_stream(futures_util::stream::once(async move {
+        let body = uncompressed
+            .lock()
+            .ok()
+            .and_then(|mut uncompressed| uncompressed.take())
+            .map(|(body, _)| body)
+            .unwrap_or_default();
+        let input = body.collect().await?.to_bytes();
+        coding
+            .encode(&input)
+            .map(Bytes::from)
+            .map_err(axum::Error::new)
+    }));
+    res
+}
@@ -0,0 +1,576 @@
+use std::borrow::Cow;
+#[cfg(feature = "serde")]
+use std::cell::LazyCell;
+use std::convert::Infallible;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use axum::extract::Request;
+#[cfg(feature = "serde")]
+use axum::http::HeaderMap;
+#[cfg(feature = "serde")]
+use axum::http::header::ACCEPT;
+use axum::http::header::{CONTENT_TYPE, IF_NONE_MATCH};
+use axum::http::request::Parts;
+use axum::http::{Extensions, HeaderValue, Method};
+use axum::response::Response;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::compression::{ContentCoding, HandlerCompression, compress_response};
+use crate::etag::conditional_response;
+use crate::named::with_message_type;
+#[cfg(feature = "serde")]
+use crate::negotiate::{FallbackFormat, Negotiation, prefers_json};
+#[cfg(feature = "serde")]
+use crate::problem;
+use crate::rejection::{Rejection, RejectionDetails, RejectionHandler, replace_body};
+use crate::rpc_status;
+use crate::{MediaType, PROTOBUF_CONTENT_TYPE, PROTOBUF_CONTENT_TYPES};
+
+static DEFAULT_CONFIG: ProtobufConfig = ProtobufConfig::new();
+
+pub(crate) const DEFAULT_COMPRESSION_THRESHOLD: usize = 1024;
+
+/// Configuration of the extractors and responses of this crate.
+///
+/// It can be attached to a router or a route, either as a layer or as a request extension:
+/// - As a layer, it configures both the extractors and the responses.
+/// - As a request extension (using axum's [`Extension`](axum::Extension) layer), it only
+///   configures the extractors.
+///
+/// When no configuration is attached, the default one is used.
+///
+/// ```rust
+/// use axum::{Router, routing::post};
+/// use axum_protobuf::{Protobuf, ProtobufConfig};
+/// # #[derive(Clone, PartialEq, prost::Message)]
+/// # struct Upload {}
+///
+/// async fn handler(Protobuf(upload): Protobuf<Upload>) {
+///     // ...
+/// }
+///
+/// let app: Router = Router::new()
+///     .route("/upload", post(handler))
+///     .layer(
+///         ProtobufConfig::new()
+///             .body_limit(16 * 1024 * 1024)
+///             .reject_empty_body(true)
+///             .response_content_type("application/x-protobuf"),
+///     );
+/// ```
+#[derive(Debug, Clone)]
+pub struct ProtobufConfig {
+    content_types: Cow<'static, [Cow<'static, str>]>,
+    response_content_type: HeaderValue,
+    pub(crate) body_limit: Option<usize>,
+    pub(crate) reject_empty_body: bool,
+    pub(crate) query_param: Cow<'static, str>,
+    pub(crate) frame_limit: usize,
+    compress_responses: bool,
+    compression_threshold: usize,
+    etag: bool,
+    rpc_status: bool,
+    #[cfg(feature = "serde")]
+    problem_details: bool,
+    pub(crate) rejection_handler: Option<RejectionHandler>,
+    decode_diagnostics: bool,
+    #[cfg(feature = "serde")]
+    pub(crate) fallback_format: FallbackFormat,
+}
+
+const DEFAULT_CONTENT_TYPES: [Cow<'static, str>; 3] = [
+    Cow::Borrowed(PROTOBUF_CONTENT_TYPES[0]),
+    Cow::Borrowed(PROTOBUF_CONTENT_TYPES[1]),
+    Cow::Borrowed(PROTOBUF_CONTENT_TYPES[2]),
+];
+
+impl ProtobufConfig {
+    /// Create the default configuration.
+    pub const fn new() -> Self {
+        ProtobufConfig {
+            content_types: Cow::Borrowed(&DEFAULT_CONTENT_TYPES),
+            response_content_type: HeaderValue::from_static(PROTOBUF_CONTENT_TYPE),
+            body_limit: None,
+            reject_empty_body: false,
+            query_param: Cow::Borrowed("message"),
+            frame_limit: 2 * 1024 * 1024,
+            compress_responses: true,
+            compression_threshold: DEFAULT_COMPRESSION_THRESHOLD,
+            etag: false,
+            rpc_status: false,
+            #[cfg(feature = "serde")]
+            problem_details: false,
+            rejection_handler: None,
+            decode_diagnostics: false,
+            #[cfg(feature = "serde")]
+            fallback_format: FallbackFormat::SameAsRequest,
+        }
+    }
+
+    /// Set the content types (without parameters) that are accepted as protobuf in requests.
+    ///
+    /// Defaults to `application/protobuf`, `application/x-protobuf` and `application/vnd.google.protobuf`.
+    /// Content types with a `+proto` suffix are always accepted.
+    pub fn content_types<I, C>(mut self, content_types: I) -> Self
+    where
+        I: IntoIterator<Item = C>,
+        C: Into<Cow<'static, str>>,
+    {
+        self.content_types = content_types
+            .into_iter()
+            .map(|c| Cow::Owned(c.into().to_ascii_lowercase()))
+            .collect();
+        self
+    }
+
+    /// Set the content type of protobuf responses.
+    ///
+    /// Defaults to `application/protobuf`.
+    /// This only has an effect when the configuration is used as a layer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the content type is not a valid header value.
+    pub fn response_content_type(mut self, content_type: &'static str) -> Self {
+        self.response_content_type = HeaderValue::from_static(content_type);
+        self
+    }
+
+    /// Set the maximum size of protobuf request bodies, in bytes.
+    ///
+    /// By default, axum's [`DefaultBodyLimit`](axum::extract::DefaultBodyLimit) applies.
+    /// When set, this limit is used instead of it for protobuf bodies
+    /// (JSON bodies of [`ProtoJson`](crate::ProtoJson) still follow `DefaultBodyLimit`).
+    ///
+    /// Note that the nesting depth of decoded messages is always limited by prost (to 100 levels).
+    pub fn body_limit(mut self, limit: usize) -> Self {
+        self.body_limit = Some(limit);
+        self
+    }
+
+    /// Reject protobuf requests that have an empty body.
+    ///
+    /// By default, an empty body is decoded as the default value of the message (as the protobuf encoding of a
+    /// message that has only default values is empty).
+    /// Optional extractors (such as `Option<Protobuf<T>>`) always give `None` for an empty body.
+    pub fn reject_empty_body(mut self, reject: bool) -> Self {
+        self.reject_empty_body = reject;
+        self
+    }
+
+    /// Set the name of the query parameter that [`ProtobufQuery`](crate::ProtobufQuery) reads the message from.
+    ///
+    /// Defaults to `message`.
+    pub fn query_param(mut self, name: impl Into<Cow<'static, str>>) -> Self {
+        self.query_param = name.into();
+        self
+    }
+
+    /// Set the maximum size of each message of a [`ProtobufStream`](crate::ProtobufStream), in bytes.
+    ///
+    /// Defaults to 2 MB.
+    pub fn frame_limit(mut self, limit: usize) -> Self {
+        self.frame_limit = limit;
+        self
+    }
+
+    /// Enable or disable the compression of protobuf responses.
+    ///
+    /// When one of the `gzip`, `deflate`, `brotli` or `zstd` features is enabled, responses are
+    /// compressed with the best coding accepted by the client (according to the `accept-encoding` header).
+    /// This also applies to the responses of [`ProtoJson::infer_response`](crate::ProtoJson::infer_response)
+    /// and [`EncodedProtobuf::infer_response`](crate::EncodedProtobuf::infer_response), which
+    /// otherwise compress themselves.
+    /// Defaults to `true`.
+    /// This only has an effect when the configuration is used as a layer.
+    pub fn compress_responses(mut self, compress: bool) -> Self {
+        self.compress_responses = compress;
+        self
+    }
+
+    /// Set the minimum size of responses to compress, in bytes.
+    ///
+    /// Compressing small responses is not worth it. Defaults to 1024 bytes.
+    pub fn compression_threshold(mut self, threshold: usize) -> Self {
+        self.compression_threshold = threshold;
+        self
+    }
+
+    /// Enable or disable conditional `GET` requests for protobuf responses.
+    ///
+    /// When enabled, a strong `ETag` header is computed from the encoded body of successful responses.
+    /// If it matches the `if-none-match` header of a `GET` or `HEAD` request, a `304 Not Modified`
+    /// response without a body is sent instead, so that clients don't download unchanged messages again.
+    ///
+    /// The encoding of messages must be deterministic for this to be useful: prost encodes `map`
+    /// fields in iteration order, so prefer `BTreeMap` to `HashMap` for them.
+    /// Streaming responses are not affected.
+    /// Defaults to `false`.
+    /// This only has an effect when the configuration is used as a layer.
+    pub fn etag(mut self, etag: bool) -> Self {
+        self.etag = etag;
+        self
+    }
+
+    /// Send rejections as `google.rpc.Status` messages.
+    ///
+    /// When enabled, the rejections of the extractors of this crate are sent as
+    /// [`google.rpc.Status`](https://google.aip.dev/193) messages instead of plain text, so that
+    /// protobuf clients can handle them like gRPC errors: the code matches the HTTP status code, and
+    /// `google.rpc.BadRequest` details describe why a field could not be decoded (when it is known).
+    /// The path of the field is only included with [`ProtobufConfig::decode_diagnostics`].
+    /// With the `serde` feature, they are encoded as JSON if the client prefers it to protobuf
+    /// (according to the `accept` header), and as protobuf otherwise.
+    /// Defaults to `false`.
+    /// This only has an effect when the configuration is used as a layer.
+    pub fn rpc_status(mut self, rpc_status: bool) -> Self {
+        self.rpc_status = rpc_status;
+        self
+    }
+
+    /// Send rejections as problem details (`application/problem+json`).
+    ///
+    /// When enabled, the rejections of the extractors of this crate are sent as
+    /// [RFC 9457](https://www.rfc-editor.org/rfc/rfc9457) problem details instead of plain text.
+    /// Their `type` is a stable URI for each rejection variant (which links to its documentation),
+    /// and their `detail` is the error that caused the rejection (such as the protobuf decoding
+    /// error, without the path of the field).
+    /// Their `errors` describe why fields could not be decoded. With
+    /// [`ProtobufConfig::decode_diagnostics`], the `detail` is the whole error, and the `errors`
+    /// contain the paths of the fields.
+    /// When [`ProtobufConfig::rpc_status`] is also enabled, clients that prefer protobuf to JSON
+    /// still get `google.rpc.Status` messages.
+    /// Defaults to `false`.
+    /// This only has an effect when the configuration is used as a layer.
+    #[cfg(feature = "serde")]
+    pub fn problem_details(mut self, problem_details: bool) -> Self {
+        self.problem_details = problem_details;
+        self
+    }
+
+    /// Set a function that constructs the responses of rejections.
+    ///
+    /// It receives the rejections of the extractors of this crate along with the parts of the
+    /// request (method, URI, headers and extensions), so that all the routes of a service can send
+    /// errors in the same format:
+    ///
+    /// ```rust
+    /// use axum::{Json, Router, response::IntoResponse, routing::post};
+    /// use axum_protobuf::{Protobuf, ProtobufConfig};
+    /// # #[derive(Clone, PartialEq, prost::Message)]
+    /// # struct Upload {}
+    ///
+    /// async fn handler(Protobuf(upload): Protobuf<Upload>) {
+    ///     // ...
+    /// }
+    ///
+    /// let app: Router = Router::new()
+    ///     .route("/upload", post(handler))
+    ///     .layer(ProtobufConfig::new().rejection_handler(|rejection, request| {
+    ///         let error = serde_json::json!({
+    ///             "error": rejection.body_text(),
+    ///             "path": request.uri.path(),
+    ///         });
+    ///         (rejection.status(), Json(error)).into_response()
+    ///     }));
+    /// ```
+    ///
+    /// The extractors keep the parts of the request when they reject it, and return a
+    /// [`HandledRejection`](crate::HandledRejection) that calls the handler when it is converted into
+    /// a response: handlers that take a `Result` of an extractor get the `Handled` variant of its
+    /// rejection, instead of the variant of the original rejection. The errors yielded by
+    /// [`ProtobufStream`](crate::ProtobufStream) are not passed to the handler.
+    ///
+    /// It takes precedence over [`ProtobufConfig::rpc_status`] and [`ProtobufConfig::problem_details`].
+    pub fn rejection_handler<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(Rejection, &Parts) -> Response + Send + Sync + 'static,
+    {
+        self.rejection_handler = Some(RejectionHandler(Arc::new(handler)));
+        self
+    }
+
+    /// Include diagnostics about decoding errors in rejection responses.
+    ///
+    /// When enabled, the plain-text body of rejections caused by a decoding error is followed by
+    /// the path of the field that could not be decoded, the wire type of the field and the
+    /// expected one (when they don't match), and the error itself.
+    /// prost doesn't report the byte offset of decoding errors, so it is not included.
+    /// With [`ProtobufConfig::rpc_status`] or [`ProtobufConfig::problem_details`], the paths of the
+    /// fields that could not be decoded are only sent when this is enabled.
+    ///
+    /// This helps debugging integrations, but reveals details about the message definitions: keep
+    /// it disabled in production.
+    /// Defaults to `false`.
+    /// This only has an effect when the configuration is used as a layer.
+    pub fn decode_diagnostics(mut self, decode_diagnostics: bool) -> Self {
+        self.decode_diagnostics = decode_diagnostics;
+        self
+    }
+
+    /// Set the format of negotiated responses to requests without an `accept` header.
+    ///
+    /// It applies to [`Negotiate`](crate::Negotiate) responses and to rejections (with
+    /// [`ProtobufConfig::rpc_status`]), while the responses constructed by handlers with
+    /// [`ProtoJson::infer_response_or`](crate::ProtoJson::infer_response_or) use their own
+    /// fallback format. Requests are left unchanged.
+    /// Negotiated responses to requests for which the fallback format can't be used get a
+    /// `400 Bad Request` response.
+    /// Defaults to [`FallbackFormat::SameAsRequest`].
+    /// This only has an effect when the configuration is used as a layer.
+    #[cfg(feature = "serde")]
+    pub fn fallback_format(mut self, fallback: FallbackFormat) -> Self {
+        self.fallback_format = fallback;
+        self
+    }
+
+    /// Get the configuration attached to a request, or the default one.
+    ///
+    /// The configuration is either added as an extension, or shared by the layer.
+    pub(crate) fn from_extensions(extensions: &Extensions) -> &Self {
+        extensions
+            .get()
+            .or_else(|| extensions.get::<Arc<Self>>().map(AsRef::as_ref))
+            .unwrap_or(&DEFAULT_CONFIG)
+    }
+
+    /// Whether a media type is accepted as protobuf in requests.
+    pub(crate) fn is_protobuf(&self, media_type: &MediaType) -> bool {
+        self.content_types.iter().any(|c| c == media_type.essence())
+            || (media_type.type_() == "application" && media_type.suffix() == Some("proto"))
+    }
+}
+
+impl Default for ProtobufConfig {
+    fn default() -> Self {
+        ProtobufConfig::new()
+    }
+}
+
+/// Marker added to the extensions of protobuf responses, so that the [`ProtobufConfig`] layer can find them.
+#[derive(Clone)]
+pub(crate) struct ProtobufResponse {
+    /// The `messageType` parameter of the content type, if any.
+    pub(crate) message_type: Option<String>,
+    /// The content type set by this crate, which is only replaced by the layer if the handler kept it.
+    pub(crate) content_type: HeaderValue,
+}
+
+impl Default for ProtobufResponse {
+    fn default() -> Self {
+        ProtobufResponse {
+            message_type: None,
+            content_type: HeaderValue::from_static(PROTOBUF_CONTENT_TYPE),
+        }
+    }
+}
+
+impl<S> Layer<S> for ProtobufConfig {
+    type Service = ProtobufConfigService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ProtobufConfigService {
+            inner,
+            config: Arc::new(self.clone()),
+        }
+    }
+}
+
+/// Middleware that applies a [`ProtobufConfig`].
+///
+/// See [`ProtobufConfig`] for more details.
+#[derive(Debug, Clone)]
+pub struct ProtobufConfigService<S> {
+    inner: S,
+    config: Arc<ProtobufConfig>,
+}
+
+impl<S> Service<Request> for ProtobufConfigService<S>
+where
+    S: Service<Request, Response = Response, Error = Infallible>,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, Infallible>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request) -> Self::Future {
+        let coding = self
+            .config
+            .compress_responses
+            .then(|| ContentCoding::negotiate(req.headers()))
+            .flatten();
+        let if_none_match: Vec<HeaderValue> =
+            if self.config.etag && matches!(*req.method(), Method::GET | Method::HEAD) {
+                req.headers()
+                    .get_all(IF_NONE_MATCH)
+                    .iter()
+                    .cloned()
+                    .collect()
+            } else {
+                Vec::new()
+            };
+        #[cfg(feature = "serde")]
+        let negotiation_headers = NegotiationHeaders::new(req.headers());
+        // A configuration added as an extension by an outer layer is replaced by this one
+        req.extensions_mut().remove::<ProtobufConfig>();
+        req.extensions_mut().insert(self.config.clone());
+        let config = self.config.clone();
+        let future = self.inner.call(req);
+
+        Box::pin(async move {
+            let mut res = future.await?;
+            #[cfg(feature = "serde")]
+            let negotiation_headers = LazyCell::new(|| negotiation_headers.header_map());
+            #[cfg(feature = "serde")]
+            let negotiated = match res.extensions_mut().remove::<Negotiation>() {
+                Some(negotiation) => {
+                    res = negotiation.respond(res, &negotiation_headers, &config);
+                    true
+                }
+                None => false,
+            };
+            #[cfg(not(feature = "serde"))]
+            let negotiated = false;
+
+            if let Some(details) = res.extensions_mut().remove::<RejectionDetails>() {
+                res = render_rejection(
+                    res,
+                    details,
+                    &config,
+                    #[cfg(feature = "serde")]
+                    &negotiation_headers,
+                );
+            }
+
+            let protobuf_response = match res.extensions().get::<ProtobufResponse>() {
+                Some(marker) => {
+                    // Content types set by handlers are kept
+                    if res.headers().get(CONTENT_TYPE) == Some(&marker.content_type) {
+                        let response_content_type = &config.response_content_type;
+                        let content_type =
+                            match (&marker.message_type, response_content_type.to_str()) {
+                                (Some(message_type), Ok(content_type)) => {
+                                    with_message_type(content_type, message_type)
+                                }
+                                _ => response_content_type.clone(),
+                            };
+                        res.headers_mut().insert(CONTENT_TYPE, content_type);
+                    }
+                    true
+                }
+                None => false,
+            };
+
+            // Responses compressed by handlers are compressed again according to the configuration
+            let handler_compression = match res.extensions_mut().remove::<HandlerCompression>() {
+                Some(compression) => {
+                    res = compression.undo(res);
+                    true
+                }
+                None => false,
+            };
+
+            if protobuf_response || negotiated || handler_compression {
+                if config.etag {
+                    res = conditional_response(
+                        res,
+                        &if_none_match,
+                        coding.filter(|_| config.compress_responses),
+                        config.compression_threshold,
+                    )
+                    .await;
+                }
+                if config.compress_responses {
+                    res = compress_response(res, coding, config.compression_threshold);
+                }
+            }
+            Ok(res)
+        })
+    }
+}
+
+/// The `accept` and `content-type` headers of a request, used to negotiate the format of responses.
+///
+/// Header values are reference-counted: copying them is cheap, and the header map is only built
+/// for the responses that are negotiated.
+#[cfg(feature = "serde")]
+struct NegotiationHeaders {
+    accept: Option<HeaderValue>,
+    more_accept: Vec<HeaderValue>,
+    content_type: Option<HeaderValue>,
+}
+
+#[cfg(feature = "serde")]
+impl NegotiationHeaders {
+    fn new(headers: &HeaderMap) -> Self {
+        let mut accept = headers.get_all(ACCEPT).iter().cloned();
+        NegotiationHeaders {
+            accept: accept.next(),
+            more_accept: accept.collect(),
+            content_type: headers.get(CONTENT_TYPE).cloned(),
+        }
+    }
+
+    fn header_map(self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in self.accept.into_iter().chain(self.more_accept) {
+            headers.append(ACCEPT, value);
+        }
+        if let Some(value) = self.content_type {
+            headers.insert(CONTENT_TYPE, value);
+        }
+        headers
+    }
+}
+
+/// Render a rejection in the format chosen by the configuration, or keep it as plain text.
+fn render_rejection(
+    res: Response,
+    details: RejectionDetails,
+    config: &ProtobufConfig,
+    #[cfg(feature = "serde")] negotiation_headers: &HeaderMap,
+) -> Response {
+    #[cfg(feature = "serde")]
+    let json = prefers_json(negotiation_headers, config);
+    #[cfg(not(feature = "serde"))]
+    let json = false;
+
+    // The paths of the fields that could not be decoded are only sent with decode diagnostics
+    let details = if config.decode_diagnostics {
+        details
+    } else {
+        details.without_diagnostics()
+    };
+    if config.rpc_status && !json {
+        return rpc_status::protobuf_response(res, &details, &config.response_content_type);
+    }
+    #[cfg(feature = "serde")]
+    {
+        if config.problem_details {
+            return problem::response(res, &details);
+        }
+        if config.rpc_status {
+            return rpc_status::json_response(res, &details);
+        }
+    }
+    if config.decode_diagnostics
+        && let Some(diagnostics) = details.diagnostics()
+    {
+        let body = format!("{}\n\n{diagnostics}\n", details.message);
+        return replace_body(
+            res,
+            body.into_bytes(),
+            HeaderValue::from_static("text/plain; charset=utf-8"),
+        );
+    }
+    res
+}
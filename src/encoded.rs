@@ -0,0 +1,207 @@
+use std::marker::PhantomData;
+
+use axum::body::{Body, Bytes};
+#[cfg(feature = "serde")]
+use axum::http::HeaderMap;
+use axum::http::StatusCode;
+use axum::http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use axum::response::{IntoResponse, Response};
+use prost::Message;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+use crate::PROTOBUF_CONTENT_TYPE;
+#[cfg(feature = "serde")]
+use crate::ProtobufConfig;
+#[cfg(feature = "serde")]
+use crate::compression::compress_handler_response;
+use crate::config::ProtobufResponse;
+#[cfg(feature = "serde")]
+use crate::negotiate::{FallbackFormat, NegotiationError, ResponseFormat, vary_accept};
+#[cfg(feature = "serde")]
+use crate::protojson::JSON_CONTENT_TYPE;
+
+/// Pre-encoded Protobuf Response.
+///
+/// This holds a message that was encoded once, so that it can be sent many times without being
+/// encoded again: cloning it only clones a reference-counted buffer ([`Bytes`]).
+/// The message type is kept in the signature of handlers.
+///
+/// ```rust
+/// use std::sync::LazyLock;
+///
+/// use axum_protobuf::EncodedProtobuf;
+/// # #[derive(Clone, PartialEq, prost::Message)]
+/// # struct Settings {}
+///
+/// static SETTINGS: LazyLock<EncodedProtobuf<Settings>> =
+///     LazyLock::new(|| EncodedProtobuf::new(&Settings {}));
+///
+/// async fn handler() -> EncodedProtobuf<Settings> {
+///     SETTINGS.clone()
+/// }
+/// ```
+///
+/// With the `serde` feature, [`EncodedProtobuf::with_json`] also encodes the message as JSON, so
+/// that [`EncodedProtobuf::infer_response`] can choose between both formats like
+/// [`ProtoJson::infer_response`](crate::ProtoJson::infer_response).
+pub struct EncodedProtobuf<T> {
+    protobuf: Bytes,
+    #[cfg(feature = "serde")]
+    json: Option<Bytes>,
+    _message: PhantomData<fn() -> T>,
+}
+
+impl<T> EncodedProtobuf<T>
+where
+    T: Message,
+{
+    /// Encode a message as protobuf.
+    pub fn new(message: &T) -> Self {
+        EncodedProtobuf {
+            protobuf: message.encode_to_vec().into(),
+            #[cfg(feature = "serde")]
+            json: None,
+            _message: PhantomData,
+        }
+    }
+}
+
+impl<T> EncodedProtobuf<T> {
+    /// The protobuf encoding of the message.
+    pub fn protobuf_bytes(&self) -> &Bytes {
+        &self.protobuf
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T> EncodedProtobuf<T>
+where
+    T: Message + Serialize,
+{
+    /// Encode a message as protobuf and as JSON (with its [`serde`] implementation).
+    ///
+    /// Returns an error if the message can't be serialized to JSON.
+    pub fn with_json(message: &T) -> Result<Self, serde_json::Error> {
+        Ok(EncodedProtobuf {
+            json: Some(serde_json::to_vec(message)?.into()),
+            ..EncodedProtobuf::new(message)
+        })
+    }
+}
+
+#[cfg(feature = "proto3-json")]
+impl<T> EncodedProtobuf<T>
+where
+    T: Message + prost_reflect::ReflectMessage,
+{
+    /// Encode a message as protobuf and as JSON, with the
+    /// [canonical proto3 JSON mapping](https://protobuf.dev/programming-guides/json/) (like
+    /// [`Proto3Json`](crate::Proto3Json)).
+    ///
+    /// Returns an error if the message can't be serialized to JSON.
+    pub fn with_proto3_json(message: &T) -> Result<Self, serde_json::Error> {
+        Ok(EncodedProtobuf {
+            json: Some(crate::proto3_json::to_vec(message)?.into()),
+            ..EncodedProtobuf::new(message)
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T> EncodedProtobuf<T> {
+    /// The JSON encoding of the message, if it was created with [`EncodedProtobuf::with_json`]
+    /// (or `EncodedProtobuf::with_proto3_json`).
+    pub fn json_bytes(&self) -> Option<&Bytes> {
+        self.json.as_ref()
+    }
+
+    /// Attempt to construct a response based on the `accept` header.
+    ///
+    /// JSON is only available if the message was created with [`EncodedProtobuf::with_json`]
+    /// (or `EncodedProtobuf::with_proto3_json`),
+    /// and the protobuf text format is not available.
+    /// See [`ProtoJson::try_infer_response`](crate::ProtoJson::try_infer_response) for more details.
+    pub fn try_infer_response(self, header_map: &HeaderMap) -> Option<Response> {
+        let format = self.negotiate(header_map, None).ok()?;
+        Some(self.into_compressed_response(format, header_map))
+    }
+
+    /// Construct a response based on the `accept` header.
+    ///
+    /// See [`ProtoJson::infer_response`](crate::ProtoJson::infer_response) for more details.
+    pub fn infer_response(self, header_map: &HeaderMap) -> Response {
+        self.negotiate_response(header_map, Some(FallbackFormat::SameAsRequest))
+    }
+
+    /// Construct a response based on the `accept` header, or in the fallback format if it is not set.
+    ///
+    /// The fallback format is only used if it is available.
+    /// See [`ProtoJson::infer_response_or`](crate::ProtoJson::infer_response_or) for more details.
+    pub fn infer_response_or(self, header_map: &HeaderMap, fallback: FallbackFormat) -> Response {
+        self.negotiate_response(header_map, Some(fallback))
+    }
+
+    fn negotiate_response(
+        self,
+        header_map: &HeaderMap,
+        fallback: Option<FallbackFormat>,
+    ) -> Response {
+        match self.negotiate(header_map, fallback) {
+            Ok(format) => self.into_compressed_response(format, header_map),
+            Err(e) => e.into_response(),
+        }
+    }
+
+    fn negotiate(
+        &self,
+        header_map: &HeaderMap,
+        fallback: Option<FallbackFormat>,
+    ) -> Result<ResponseFormat, NegotiationError> {
+        ResponseFormat::negotiate(header_map, fallback, &ProtobufConfig::new(), |format| {
+            match format {
+                ResponseFormat::Json => self.json.is_some(),
+                ResponseFormat::Protobuf => true,
+                #[cfg(feature = "text-format")]
+                ResponseFormat::TextFormat(_) => false,
+            }
+        })
+    }
+
+    fn into_compressed_response(self, format: ResponseFormat, header_map: &HeaderMap) -> Response {
+        let res = match (format, &self.json) {
+            (ResponseFormat::Json, Some(json)) => Response::builder()
+                .status(StatusCode::OK)
+                .header(CONTENT_TYPE, JSON_CONTENT_TYPE)
+                .header(CONTENT_LENGTH, json.len())
+                .body(Body::from(json.clone()))
+                .unwrap(), // we know this will be valid since we made it
+            _ => self.into_response(),
+        };
+
+        compress_handler_response(vary_accept(res), header_map)
+    }
+}
+
+impl<T> Clone for EncodedProtobuf<T> {
+    fn clone(&self) -> Self {
+        EncodedProtobuf {
+            protobuf: self.protobuf.clone(),
+            #[cfg(feature = "serde")]
+            json: self.json.clone(),
+            _message: PhantomData,
+        }
+    }
+}
+
+impl<T> IntoResponse for EncodedProtobuf<T> {
+    fn into_response(self) -> Response {
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, PROTOBUF_CONTENT_TYPE)
+            .header(CONTENT_LENGTH, self.protobuf.len())
+            .extension(ProtobufResponse::default())
+            .body(Body::from(self.protobuf))
+            .unwrap() // we know this will be valid since we made it
+    }
+}
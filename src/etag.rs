@@ -0,0 +1,82 @@
+use axum::body::{Body, HttpBody};
+use axum::http::header::{CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, ETAG};
+use axum::http::{HeaderValue, StatusCode};
+use axum::response::Response;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use http_body_util::BodyExt;
+
+use crate::compression::ContentCoding;
+
+/// Add a strong `ETag` header to a successful response, computed from its body.
+///
+/// `coding` is the coding that will be used to compress the response (if it is large enough),
+/// as each encoding of the body is a different representation, with its own entity tag.
+///
+/// If the entity tag matches the `if-none-match` header of the request, a `304 Not Modified`
+/// response without a body is returned instead.
+pub(crate) async fn conditional_response(
+    res: Response,
+    if_none_match: &[HeaderValue],
+    coding: Option<ContentCoding>,
+    compression_threshold: usize,
+) -> Response {
+    if res.status() != StatusCode::OK
+        || res.headers().contains_key(ETAG)
+        || res.body().size_hint().exact().is_none()
+    {
+        return res;
+    }
+
+    let (mut parts, body) = res.into_parts();
+    let Ok(body) = body.collect().await.map(|body| body.to_bytes()) else {
+        return Response::builder()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body(Body::empty())
+            .unwrap(); // we know this will be valid since we made it
+    };
+
+    let coding = coding.filter(|_| {
+        !parts.headers.contains_key(CONTENT_ENCODING) && body.len() >= compression_threshold
+    });
+    let etag = entity_tag(&body, coding);
+
+    if if_none_match
+        .iter()
+        .any(|value| matches_if_none_match(value, &etag))
+    {
+        parts.status = StatusCode::NOT_MODIFIED;
+        parts.headers.remove(CONTENT_LENGTH);
+        parts.headers.remove(CONTENT_TYPE);
+        parts.headers.insert(ETAG, etag);
+        return Response::from_parts(parts, Body::empty());
+    }
+
+    parts.headers.insert(ETAG, etag);
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// Compute a strong entity tag from a 128-bit hash of the body, followed by the content coding, if any.
+fn entity_tag(body: &[u8], coding: Option<ContentCoding>) -> HeaderValue {
+    let hash = URL_SAFE_NO_PAD.encode(xxhash_rust::xxh3::xxh3_128(body).to_be_bytes());
+    let etag = match coding {
+        Some(coding) => format!(
+            "\"{hash}-{}\"",
+            coding.header_value().to_str().unwrap_or_default()
+        ),
+        None => format!("\"{hash}\""),
+    };
+    HeaderValue::try_from(etag).unwrap() // we know this will be valid since we made it
+}
+
+/// Whether an `if-none-match` header matches an entity tag, using the weak comparison.
+fn matches_if_none_match(value: &HeaderValue, etag: &HeaderValue) -> bool {
+    let Ok(value) = value.to_str() else {
+        return false;
+    };
+    let etag = etag.to_str().unwrap_or_default();
+
+    value.split(',').map(str::trim).any(|candidate| {
+        candidate == "*" || candidate.strip_prefix("W/").unwrap_or(candidate) == etag
+    })
+}
@@ -10,29 +10,134 @@
 //!
 //! The only difference is that `T` must implement [prost::Message](https://docs.rs/prost/latest/prost/trait.Message.html).
 //!
+//! For messages that implement [prost::Name](https://docs.rs/prost/latest/prost/trait.Name.html), [`NamedProtobuf`] also sets
+//! and verifies the `messageType` parameter of the `content-type` header.
+//!
+//! To send a shared message (such as an `Arc<T>`) without cloning it, use [`SharedProtobuf`].
+//!
+//! ## EncodedProtobuf Response
+//!
+//! To send the same message many times, [`EncodedProtobuf`] encodes it once (optionally as JSON too) and can be cheaply cloned.
+//!
+//! ## Request Decompression
+//!
+//! With the `gzip`, `deflate`, `brotli` and `zstd` features, the [`Protobuf`] extractor decompresses request bodies according to their `content-encoding` header.
+//! The body limit applies to the decompressed body.
+//!
+//! ## Response Compression
+//!
+//! With the same features, responses are compressed according to the `accept-encoding` header of the request:
+//! - [`ProtoJson::infer_response`] does it on its own, or follows the [`ProtobufConfig`] layer when it is applied.
+//! - [`Protobuf`] responses need the [`ProtobufConfig`] layer.
+//!
+//! ## Configuration
+//!
+//! The extractors and responses can be configured for a router or a route using [`ProtobufConfig`].
+//!
+//! ## Conditional Requests
+//!
+//! With [`ProtobufConfig::etag`], [`Protobuf`] responses get a strong `ETag` header, and `GET` requests
+//! with a matching `if-none-match` header get a `304 Not Modified` response without a body.
+//!
+//! ## Rejections
+//!
+//! With [`ProtobufConfig::rpc_status`], rejections are sent as `google.rpc.Status` messages (in protobuf or JSON,
+//! according to the `accept` header), with `google.rpc.BadRequest` details about the field that could not be decoded.
+//! With [`ProtobufConfig::problem_details`], they are sent as [RFC 9457](https://www.rfc-editor.org/rfc/rfc9457) problem details
+//! (`application/problem+json`), with a stable `type` URI for each rejection and the error that caused it. When both are enabled, clients that prefer protobuf still get `google.rpc.Status` messages.
+//!
+//! To send rejections in another format, set a function with [`ProtobufConfig::rejection_handler`]: it receives each
+//! [`Rejection`] along with the parts of the request, and constructs the response.
+//!
+//! To debug integrations, [`ProtobufConfig::decode_diagnostics`] adds the path of the field that could not be decoded, the wire type mismatch and the decoding error to plain-text rejections. It also adds the path of the field to `google.rpc.Status` messages and problem details, and the whole decoding error to problem details. It is disabled by default, and should stay disabled in production.
+//!
+//! ## ProtobufStream Extractor
+//!
+//! For bulk-ingest endpoints, the [`ProtobufStream`] extractor decodes a request body made of length-delimited protobuf messages as a stream, without buffering the whole body.
+//!
+//! ## ProtobufStreamResponse
+//!
+//! To send large result sets, the [`ProtobufStreamResponse`] response encodes a stream of messages as length-delimited protobuf messages, as they are produced.
+//!
+//! ## ProtobufQuery Extractor
+//!
+//! For `GET` requests, the [`ProtobufQuery`] extractor decodes a base64url-encoded protobuf message from the query string.
+//!
 //! ## ProtoJson Extractor
 //!
 //! Additionally, this crate provides a [`ProtoJson`] extractor that can extract both protocol buffers and JSON payloads, depending upon the `content-type` header.
 //!
 //! Note that this does not implement [IntoResponse](https://docs.rs/axum/latest/axum/response/trait.IntoResponse.html) but you can use [`ProtoJson::infer_response`] to convert it into a JSON or protobuf response, based upon the `accept` header.
 //! Otherwise, you can simply convert `ProtoJson` to `Json` or `Protobuf`.
+//! Handlers can also return [`Negotiate`], whose format is chosen by the [`ProtobufConfig`] layer based upon the `accept` header.
+//! Responses mirror the format of the request body (which handlers can read with the [`WireFormat`] extractor) when the `accept` header is missing, unless another [`FallbackFormat`] is set with [`ProtobufConfig::fallback_format`] for `Negotiate` (or passed to [`ProtoJson::infer_response_or`]).
+//!
+//! With the `text-format` feature, the `ProtoJsonText` extractor also supports the [protobuf text format](https://protobuf.dev/reference/protobuf/textformat-spec/)
+//! (`text/x-protobuf` or `application/x-protobuf-text`) as a third format, for requests and responses (including `Negotiate<ProtoJsonText<T>>`).
+//! Its messages must implement [prost_reflect::ReflectMessage](https://docs.rs/prost-reflect/latest/prost_reflect/trait.ReflectMessage.html).
+//!
+//! With the `proto3-json` feature, the `Proto3Json` extractor works like `ProtoJson`, but its JSON follows the [canonical proto3 JSON mapping](https://protobuf.dev/programming-guides/json/)
+//! instead of the `serde` implementation of messages, so that clients using the official protobuf libraries can understand it (also with `Negotiate<Proto3Json<T>>` and `EncodedProtobuf::with_proto3_json`).
+//! Its messages must implement [prost_reflect::ReflectMessage](https://docs.rs/prost-reflect/latest/prost_reflect/trait.ReflectMessage.html).
 
 // Force exposed items to be documented
 #![deny(missing_docs)]
 
-use axum::body::Body;
-use axum::extract::FromRequest;
+use std::convert::Infallible;
+use std::fmt;
+
+use axum::RequestExt;
+use axum::body::{Body, Bytes, HttpBody};
+use axum::extract::{FromRequest, OptionalFromRequest};
 use axum::http::StatusCode;
-use axum::http::header::CONTENT_TYPE;
+use axum::http::header::{CONTENT_LENGTH, CONTENT_TYPE};
 use axum::response::{IntoResponse, Response};
-use futures_util::StreamExt;
+use bytes::BytesMut;
+use http_body_util::{BodyExt, LengthLimitError, Limited};
 use prost::Message;
 
+mod compression;
+mod config;
+mod encoded;
+mod etag;
+mod media_type;
+mod named;
+#[cfg(feature = "serde")]
+mod negotiate;
+#[cfg(feature = "serde")]
+mod problem;
+#[cfg(feature = "proto3-json")]
+mod proto3_json;
 #[cfg(feature = "serde")]
 mod protojson;
+mod query;
+mod rejection;
+mod rpc_status;
+mod shared;
+mod stream;
+#[cfg(feature = "text-format")]
+mod text_format;
+
+use crate::compression::ContentCoding;
+use crate::config::ProtobufResponse;
+use crate::rejection::{RejectionDetails, rejection_response};
 
+pub use crate::config::{ProtobufConfig, ProtobufConfigService};
+pub use crate::encoded::EncodedProtobuf;
+pub use crate::media_type::MediaType;
+pub use crate::named::NamedProtobuf;
+#[cfg(feature = "serde")]
+pub use crate::negotiate::{FallbackFormat, Negotiate};
+#[cfg(feature = "proto3-json")]
+pub use crate::proto3_json::Proto3Json;
 #[cfg(feature = "serde")]
 pub use crate::protojson::*;
+pub use crate::query::ProtobufQuery;
+pub use crate::rejection::{HandledRejection, Rejection};
+pub use crate::shared::SharedProtobuf;
+pub use crate::stream::{ProtobufStream, ProtobufStreamResponse};
+#[cfg(feature = "text-format")]
+pub use crate::text_format::ProtoJsonText;
 
 const PROTOBUF_CONTENT_TYPES: [&str; 3] = [
     "application/protobuf",
@@ -42,6 +147,11 @@ const PROTOBUF_CONTENT_TYPES: [&str; 3] = [
 const PROTOBUF_CONTENT_TYPE: &str = PROTOBUF_CONTENT_TYPES[0];
 
 /// Possible reasons why a request could be rejected.
+///
+/// When a [`ProtobufConfig::rejection_handler`] is set, every rejection is wrapped in
+/// [`ProtobufRejection::Handled`]: matches on the other variants don't apply, and the original
+/// rejection is available with [`HandledRejection::rejection`].
+#[derive(Debug)]
 pub enum ProtobufRejection {
     /// Decoding Protobuf failed.
     ProtobufDecodeError(prost::DecodeError),
@@ -51,10 +161,89 @@ pub enum ProtobufRejection {
 
     /// Protobuf Content-Type header is missing.
     MissingProtobufContentType,
+
+    /// Request body is larger than the configured limit.
+    PayloadTooLarge,
+
+    /// Query parameter containing the Protobuf message is missing.
+    MissingQueryParameter,
+
+    /// Query parameter containing the Protobuf message is not valid base64url.
+    InvalidBase64QueryParameter,
+
+    /// Request body is empty, which is not allowed by the configuration.
+    EmptyBody,
+
+    /// Content-Encoding header has an unsupported value.
+    UnsupportedContentEncoding,
+
+    /// Decompressing request body failed.
+    FailedToDecompressBody,
+
+    /// The `messageType` parameter of the Content-Type header doesn't match the expected message.
+    MessageTypeMismatch,
+
+    /// The request was rejected, and the response is constructed by the rejection handler set with
+    /// [`ProtobufConfig::rejection_handler`].
+    Handled(HandledRejection),
 }
-impl IntoResponse for ProtobufRejection {
-    fn into_response(self) -> Response {
-        let (status, body) = match self {
+impl ProtobufRejection {
+    /// Get the status code used for the response of this rejection.
+    ///
+    /// For [`Handled`](Self::Handled) rejections, this is the status code of the original
+    /// rejection: the response is constructed by the rejection handler.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ProtobufRejection::Handled(rejection) => rejection.rejection().status(),
+            _ => self.parts().0,
+        }
+    }
+
+    /// Get the body text used for the response of this rejection.
+    ///
+    /// For [`Handled`](Self::Handled) rejections, this is the body text of the original
+    /// rejection: the response is constructed by the rejection handler.
+    pub fn body_text(&self) -> String {
+        match self {
+            ProtobufRejection::Handled(rejection) => rejection.rejection().body_text(),
+            _ => self.parts().1.to_owned(),
+        }
+    }
+
+    pub(crate) fn details(&self) -> RejectionDetails {
+        if let ProtobufRejection::Handled(rejection) = self {
+            return rejection.rejection().details();
+        }
+        let (_, title) = self.parts();
+        let details = RejectionDetails::new(
+            ("ProtobufRejection", self.variant_name()),
+            title,
+            title.to_owned(),
+        );
+        match self {
+            ProtobufRejection::ProtobufDecodeError(e) => details.with_decode_error(e),
+            _ => details,
+        }
+    }
+
+    fn variant_name(&self) -> &'static str {
+        match self {
+            ProtobufRejection::ProtobufDecodeError(_) => "ProtobufDecodeError",
+            ProtobufRejection::FailedToBufferBody => "FailedToBufferBody",
+            ProtobufRejection::MissingProtobufContentType => "MissingProtobufContentType",
+            ProtobufRejection::PayloadTooLarge => "PayloadTooLarge",
+            ProtobufRejection::MissingQueryParameter => "MissingQueryParameter",
+            ProtobufRejection::InvalidBase64QueryParameter => "InvalidBase64QueryParameter",
+            ProtobufRejection::EmptyBody => "EmptyBody",
+            ProtobufRejection::UnsupportedContentEncoding => "UnsupportedContentEncoding",
+            ProtobufRejection::FailedToDecompressBody => "FailedToDecompressBody",
+            ProtobufRejection::MessageTypeMismatch => "MessageTypeMismatch",
+            ProtobufRejection::Handled(_) => "Handled",
+        }
+    }
+
+    fn parts(&self) -> (StatusCode, &'static str) {
+        match self {
             ProtobufRejection::ProtobufDecodeError(_) => {
                 (StatusCode::BAD_REQUEST, "Protobuf decoding error")
             }
@@ -66,12 +255,64 @@ impl IntoResponse for ProtobufRejection {
                 StatusCode::UNSUPPORTED_MEDIA_TYPE,
                 "Missing 'content-type: application/protobuf' header",
             ),
-        };
-
-        Response::builder()
-            .status(status)
-            .body(Body::from(body))
-            .unwrap() // we know this will be valid since we made it
+            ProtobufRejection::PayloadTooLarge => {
+                (StatusCode::PAYLOAD_TOO_LARGE, "Request body is too large")
+            }
+            ProtobufRejection::MissingQueryParameter => (
+                StatusCode::BAD_REQUEST,
+                "Missing query parameter containing the protobuf message",
+            ),
+            ProtobufRejection::InvalidBase64QueryParameter => (
+                StatusCode::BAD_REQUEST,
+                "Query parameter containing the protobuf message is not valid base64url",
+            ),
+            ProtobufRejection::EmptyBody => (StatusCode::BAD_REQUEST, "Request body is empty"),
+            ProtobufRejection::UnsupportedContentEncoding => (
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "Unsupported 'content-encoding' header",
+            ),
+            ProtobufRejection::FailedToDecompressBody => {
+                (StatusCode::BAD_REQUEST, "Error decompressing request body")
+            }
+            ProtobufRejection::MessageTypeMismatch => (
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "Unexpected 'messageType' parameter in 'content-type' header",
+            ),
+            ProtobufRejection::Handled(rejection) => (
+                rejection.rejection().status(),
+                rejection.rejection().details().title,
+            ),
+        }
+    }
+}
+impl IntoResponse for ProtobufRejection {
+    fn into_response(self) -> Response {
+        match self {
+            ProtobufRejection::Handled(rejection) => rejection.into_response(),
+            _ => rejection_response(Rejection::Protobuf(self)),
+        }
+    }
+}
+impl fmt::Display for ProtobufRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtobufRejection::Handled(rejection) => rejection.fmt(f),
+            _ => f.write_str(self.parts().1),
+        }
+    }
+}
+impl std::error::Error for ProtobufRejection {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ProtobufRejection::ProtobufDecodeError(e) => Some(e),
+            ProtobufRejection::Handled(rejection) => Some(rejection.rejection()),
+            _ => None,
+        }
+    }
+}
+impl From<HandledRejection> for ProtobufRejection {
+    fn from(rejection: HandledRejection) -> Self {
+        ProtobufRejection::Handled(rejection)
     }
 }
 
@@ -81,9 +322,26 @@ impl IntoResponse for ProtobufRejection {
 ///
 /// The request will be rejected (and a [`ProtobufRejection`] will be returned) if:
 /// - The request doesn't have a `Content-Type: application/protobuf` (or similar) header.
+///   Parameters are allowed and matching is case-insensitive; the parameters can be read using
+///   the [`MediaType`] extractor.
 /// - The request body failed to decode into the expected protobuf type.
+/// - The request body is larger than the limit set by axum's
+///   [`DefaultBodyLimit`](axum::extract::DefaultBodyLimit) (2 MB unless configured otherwise).
+/// - The request has a `Content-Encoding` header that is not supported, or decompressing the body fails.
 /// - Buffering the request body fails.
 ///
+/// Compressed request bodies are decompressed according to the `Content-Encoding` header, when the
+/// corresponding feature is enabled: `gzip`, `deflate`, `brotli` (`br`) and `zstd`.
+/// The body limit applies to the decompressed body.
+///
+/// Use [`LimitedProtobuf`] to override the body limit for a single extractor.
+/// See [`ProtobufConfig`] to configure this extractor (and the response) for a router or a route.
+///
+/// When used as a response, it encodes any type that implements [`prost::Message`] (including
+/// `Box<T>`) with an accurate `Content-Length` header.
+/// Use [`SharedProtobuf`] to send a message behind a pointer (such as `Arc<T>` or `&'static T`)
+/// without cloning it.
+///
 /// ⚠️ Since parsing Protobuf requires consuming the request body, the [`Protobuf`] extractor must be
 /// *last* if there are multiple extractors in a handler.
 /// See ["the order of extractors"](https://docs.rs/axum/latest/axum/extract/index.html#the-order-of-extractors).
@@ -91,25 +349,33 @@ pub struct Protobuf<T>(pub T);
 
 impl<T> IntoResponse for Protobuf<T>
 where
-    T: Message + Default,
+    T: Message,
 {
     fn into_response(self) -> Response {
-        let mut buf = Vec::new();
-
-        if let Err(e) = self.0.encode(&mut buf) {
-            buf = format!("protobuf encoding error: {e}").into_bytes();
-
-            Response::builder()
-                .status(StatusCode::INTERNAL_SERVER_ERROR)
-                .body(Body::from(buf))
-                .unwrap() // we know this will be valid since we made it
-        } else {
-            Response::builder()
-                .status(StatusCode::OK)
-                .header(CONTENT_TYPE, PROTOBUF_CONTENT_TYPE)
-                .body(Body::from(buf))
-                .unwrap() // we know this will be valid since we made it
-        }
+        encode_response(&self.0)
+    }
+}
+
+/// Construct a protobuf response with an encoded message.
+pub(crate) fn encode_response<T>(message: &T) -> Response
+where
+    T: Message,
+{
+    let mut buf = BytesMut::with_capacity(message.encoded_len());
+
+    if let Err(e) = message.encode(&mut buf) {
+        Response::builder()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body(Body::from(format!("protobuf encoding error: {e}")))
+            .unwrap() // we know this will be valid since we made it
+    } else {
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, PROTOBUF_CONTENT_TYPE)
+            .header(CONTENT_LENGTH, buf.len())
+            .extension(ProtobufResponse::default())
+            .body(Body::from(buf.freeze()))
+            .unwrap() // we know this will be valid since we made it
     }
 }
 impl<S, T> FromRequest<S> for Protobuf<T>
@@ -119,23 +385,238 @@ where
 {
     type Rejection = ProtobufRejection;
 
-    async fn from_request(req: axum::http::Request<Body>, _: &S) -> Result<Self, Self::Rejection> {
-        req.headers()
-            .get(CONTENT_TYPE)
-            .and_then(|value| value.to_str().ok())
-            .filter(|value| PROTOBUF_CONTENT_TYPES.contains(value))
-            .ok_or(ProtobufRejection::MissingProtobufContentType)?;
+    async fn from_request(
+        mut req: axum::http::Request<Body>,
+        _: &S,
+    ) -> Result<Self, Self::Rejection> {
+        decode_request(&mut req, None)
+            .await
+            .map(|x| Self(x))
+            .map_err(|rejection| rejection::handle(rejection, req.into_parts().0))
+    }
+}
+/// A missing `content-type` header or an empty body gives `None`.
+/// Other errors are still rejected.
+impl<S, T> OptionalFromRequest<S> for Protobuf<T>
+where
+    T: Message + Default,
+    S: Send + Sync,
+{
+    type Rejection = ProtobufRejection;
 
-        let mut body = req.into_body().into_data_stream();
-        let mut buf = Vec::new();
+    async fn from_request(
+        mut req: axum::http::Request<Body>,
+        _: &S,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        decode_optional_request(&mut req)
+            .await
+            .map(|x| x.map(Self))
+            .map_err(|rejection| rejection::handle(rejection, req.into_parts().0))
+    }
+}
 
-        while let Some(chunk) = body.next().await {
-            let chunk = chunk.map_err(|_| ProtobufRejection::FailedToBufferBody)?;
-            buf.extend_from_slice(&chunk);
-        }
+/// Protobuf Extractor with a custom body limit.
+///
+/// This behaves exactly like the [`Protobuf`] extractor, except that the request body is limited
+/// to `LIMIT` bytes, regardless of the [`DefaultBodyLimit`](axum::extract::DefaultBodyLimit)
+/// or [`ProtobufConfig::body_limit`] that might be in place.
+///
+/// ```rust
+/// use axum_protobuf::LimitedProtobuf;
+/// # #[derive(Clone, PartialEq, prost::Message)]
+/// # struct Upload {}
+///
+/// // Accept uploads up to 16 MB
+/// async fn handler(LimitedProtobuf(upload): LimitedProtobuf<Upload, { 16 * 1024 * 1024 }>) {
+///     // ...
+/// }
+/// ```
+pub struct LimitedProtobuf<T, const LIMIT: usize>(pub T);
 
-        T::decode(buf.as_slice())
+impl<S, T, const LIMIT: usize> FromRequest<S> for LimitedProtobuf<T, LIMIT>
+where
+    T: Message + Default,
+    S: Send + Sync,
+{
+    type Rejection = ProtobufRejection;
+
+    async fn from_request(
+        mut req: axum::http::Request<Body>,
+        _: &S,
+    ) -> Result<Self, Self::Rejection> {
+        decode_request(&mut req, Some(LIMIT))
+            .await
             .map(|x| Self(x))
-            .map_err(ProtobufRejection::ProtobufDecodeError)
+            .map_err(|rejection| rejection::handle(rejection, req.into_parts().0))
+    }
+}
+impl<T, const LIMIT: usize> From<LimitedProtobuf<T, LIMIT>> for Protobuf<T> {
+    fn from(val: LimitedProtobuf<T, LIMIT>) -> Self {
+        Protobuf(val.0)
+    }
+}
+
+fn content_length(req: &axum::http::Request<Body>) -> Option<u64> {
+    req.headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+}
+
+/// Decode a protobuf request body, checking its `content-type` header and enforcing the body limit.
+///
+/// The body is taken from the request, so that the rest of the request can be passed to the
+/// rejection handler.
+pub(crate) async fn decode_request<T>(
+    req: &mut axum::http::Request<Body>,
+    limit: Option<usize>,
+) -> Result<T, ProtobufRejection>
+where
+    T: Message + Default,
+{
+    let reject_empty_body = ProtobufConfig::from_extensions(req.extensions()).reject_empty_body;
+    let buf = buffer_request(req, limit).await?;
+
+    decode_message(buf, reject_empty_body)
+}
+
+/// Decode an optional protobuf request body.
+///
+/// A missing `content-type` header or an empty body gives `None`.
+pub(crate) async fn decode_optional_request<T>(
+    req: &mut axum::http::Request<Body>,
+) -> Result<Option<T>, ProtobufRejection>
+where
+    T: Message + Default,
+{
+    if !req.headers().contains_key(CONTENT_TYPE) {
+        return Ok(None);
+    }
+
+    let buf = buffer_request(req, None).await?;
+    if buf.is_empty() {
+        return Ok(None);
+    }
+
+    T::decode(buf)
+        .map(Some)
+        .map_err(ProtobufRejection::ProtobufDecodeError)
+}
+
+/// Check the `content-type` header and buffer the request body, while enforcing the body limit.
+///
+/// If no explicit limit is given, the one from [`ProtobufConfig`] is used, or the default body limit.
+async fn buffer_request(
+    req: &mut axum::http::Request<Body>,
+    limit: Option<usize>,
+) -> Result<Bytes, ProtobufRejection> {
+    let config = ProtobufConfig::from_extensions(req.extensions());
+    MediaType::from_content_type(req.headers())
+        .filter(|media_type| config.is_protobuf(media_type))
+        .ok_or(ProtobufRejection::MissingProtobufContentType)?;
+
+    buffer_body(req, limit).await
+}
+
+/// Buffer a request body, decompressing it and enforcing the body limit.
+///
+/// The body is taken from the request, which keeps its other parts.
+pub(crate) async fn buffer_body(
+    req: &mut axum::http::Request<Body>,
+    limit: Option<usize>,
+) -> Result<Bytes, ProtobufRejection> {
+    let codings = ContentCoding::from_headers(req.headers())?;
+    let limit = match limit.or(ProtobufConfig::from_extensions(req.extensions()).body_limit) {
+        Some(limit) => Some(limit),
+        None => default_body_limit(req),
+    };
+    if let (Some(length), Some(limit)) = (content_length(req), limit)
+        && length > limit as u64
+    {
+        return Err(ProtobufRejection::PayloadTooLarge);
+    }
+
+    let mut buf = collect_body(limited_body(req, limit)).await?;
+    for coding in codings.iter().rev() {
+        buf = coding.decode(&buf, limit)?;
+    }
+    Ok(buf)
+}
+
+/// Buffer a JSON request body like axum's [`Json`](axum::Json) extractor, with the same rejections.
+///
+/// The body is taken from the request, which keeps its other parts: its extensions are copied,
+/// so that axum enforces the default body limit.
+#[cfg(feature = "serde")]
+pub(crate) async fn buffer_json_body(
+    req: &mut axum::http::Request<Body>,
+) -> Result<Bytes, axum::extract::rejection::JsonRejection> {
+    let mut body_req = axum::http::Request::new(std::mem::take(req.body_mut()));
+    *body_req.extensions_mut() = req.extensions().clone();
+    Ok(Bytes::from_request(body_req, &()).await?)
+}
+
+/// Take the body of a request, wrapped in [`Limited`] if there is a limit.
+fn limited_body(req: &mut axum::http::Request<Body>, limit: Option<usize>) -> Body {
+    let body = std::mem::take(req.body_mut());
+    match limit {
+        Some(limit) => Body::new(Limited::new(body, limit)),
+        None => body,
+    }
+}
+
+/// Find the limit set by axum's [`DefaultBodyLimit`](axum::extract::DefaultBodyLimit), as it is not exposed.
+///
+/// When a limit is in place, `with_limited_body` wraps the body in `Limited`, which sets the
+/// upper bound of the size hint of an unbounded body to the limit: the extensions of the request
+/// are moved to a request with an unbounded body while the limit is applied, then restored.
+fn default_body_limit(req: &mut axum::http::Request<Body>) -> Option<usize> {
+    let unbounded = futures_util::stream::empty::<Result<Bytes, Infallible>>();
+    let mut probe = axum::http::Request::new(Body::from_stream(unbounded));
+    std::mem::swap(probe.extensions_mut(), req.extensions_mut());
+    let mut probe = probe.with_limited_body();
+    std::mem::swap(probe.extensions_mut(), req.extensions_mut());
+    probe
+        .body()
+        .size_hint()
+        .upper()
+        .and_then(|upper| usize::try_from(upper).ok())
+}
+
+fn decode_message<T>(buf: Bytes, reject_empty_body: bool) -> Result<T, ProtobufRejection>
+where
+    T: Message + Default,
+{
+    if reject_empty_body && buf.is_empty() {
+        return Err(ProtobufRejection::EmptyBody);
+    }
+
+    T::decode(buf).map_err(ProtobufRejection::ProtobufDecodeError)
+}
+
+async fn collect_body(body: Body) -> Result<Bytes, ProtobufRejection> {
+    // Collecting the body keeps the received chunks as they are: a body made of a single chunk is
+    // decoded without any copy, and `bytes::Bytes` fields of the message can borrow from it.
+    body.collect()
+        .await
+        .map(|collected| collected.to_bytes())
+        .map_err(|e| {
+            if is_length_limit_error(&e) {
+                ProtobufRejection::PayloadTooLarge
+            } else {
+                ProtobufRejection::FailedToBufferBody
+            }
+        })
+}
+
+/// Look for a [`LengthLimitError`] in the chain of errors, as it can be wrapped several times.
+fn is_length_limit_error(error: &axum::Error) -> bool {
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(error);
+    while let Some(e) = source {
+        if e.is::<LengthLimitError>() {
+            return true;
+        }
+        source = e.source();
     }
+    false
 }
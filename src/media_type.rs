@@ -0,0 +1,185 @@
+use std::convert::Infallible;
+
+use axum::extract::OptionalFromRequestParts;
+use axum::http::HeaderMap;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::request::Parts;
+
+/// A parsed media type, as found in the `content-type` header.
+///
+/// Type, subtype and parameter names are case-insensitive: they are normalized to lowercase.
+/// Parameter values are kept as they are (without quotes).
+///
+/// It can be used as an `Option<MediaType>` extractor, to read the parameters of the
+/// `content-type` header of a request (alongside a [`Protobuf`](crate::Protobuf) extractor for example).
+///
+/// ```rust
+/// use axum_protobuf::MediaType;
+///
+/// let media_type = MediaType::parse("Application/X-Protobuf; messageType=\"foo.Bar\"").unwrap();
+/// assert_eq!(media_type.essence(), "application/x-protobuf");
+/// assert_eq!(media_type.param("messagetype"), Some("foo.Bar"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaType {
+    essence: String,
+    slash: usize,
+    params: Vec<(String, String)>,
+}
+
+impl MediaType {
+    /// Parse a media type (such as `application/json; charset=utf-8`).
+    ///
+    /// Returns `None` if the input is not a valid media type.
+    pub fn parse(input: &str) -> Option<Self> {
+        let (essence, mut rest) = input.split_at(input.find(';').unwrap_or(input.len()));
+        let (type_, subtype) = essence.trim_matches(is_whitespace).split_once('/')?;
+        if !is_token(type_) || !is_token(subtype) {
+            return None;
+        }
+
+        let mut params = Vec::new();
+        loop {
+            rest = rest.trim_start_matches(is_whitespace);
+            if rest.is_empty() {
+                break;
+            }
+            rest = rest.strip_prefix(';')?.trim_start_matches(is_whitespace);
+            // Tolerate empty parameters (like in `text/plain;;charset=utf-8` or a trailing `;`)
+            if rest.is_empty() || rest.starts_with(';') {
+                continue;
+            }
+
+            let (name, value) = rest.split_once('=')?;
+            if !is_token(name) {
+                return None;
+            }
+            let value = if let Some(quoted) = value.strip_prefix('"') {
+                let (value, remaining) = parse_quoted_string(quoted)?;
+                rest = remaining;
+                value
+            } else {
+                let end = value
+                    .find(|c| c == ';' || is_whitespace(c))
+                    .unwrap_or(value.len());
+                let (value, remaining) = value.split_at(end);
+                if !is_token(value) {
+                    return None;
+                }
+                rest = remaining;
+                value.to_owned()
+            };
+            params.push((name.to_ascii_lowercase(), value));
+        }
+
+        Some(MediaType {
+            essence: format!("{type_}/{subtype}").to_ascii_lowercase(),
+            slash: type_.len(),
+            params,
+        })
+    }
+
+    /// The media type without its parameters (such as `application/json`).
+    pub fn essence(&self) -> &str {
+        &self.essence
+    }
+
+    /// The top-level type (such as `application`).
+    pub fn type_(&self) -> &str {
+        &self.essence[..self.slash]
+    }
+
+    /// The subtype, including its suffix (such as `json` or `vnd.api+json`).
+    pub fn subtype(&self) -> &str {
+        &self.essence[self.slash + 1..]
+    }
+
+    /// The structured syntax suffix of the subtype, if any (such as `json` for `application/vnd.api+json`).
+    pub fn suffix(&self) -> Option<&str> {
+        self.subtype().rsplit_once('+').map(|(_, suffix)| suffix)
+    }
+
+    /// The value of a parameter, looked up case-insensitively.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// All parameters, in order of appearance.
+    pub fn params(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.params.iter().map(|(n, v)| (n.as_str(), v.as_str()))
+    }
+
+    /// Whether this media type designates JSON.
+    #[cfg(feature = "serde")]
+    pub(crate) fn is_json(&self) -> bool {
+        self.type_() == "application" && (self.subtype() == "json" || self.suffix() == Some("json"))
+    }
+
+    /// Parse the `content-type` header, if any.
+    pub(crate) fn from_content_type(headers: &HeaderMap) -> Option<Self> {
+        headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(MediaType::parse)
+    }
+}
+
+impl<S> OptionalFromRequestParts<S> for MediaType
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Option<Self>, Self::Rejection> {
+        Ok(MediaType::from_content_type(&parts.headers))
+    }
+}
+
+/// Parse a [q-value](https://www.rfc-editor.org/rfc/rfc9110#name-quality-values), in thousandths.
+///
+/// Returns `None` if it is not between `0` and `1`, with at most 3 decimals.
+pub(crate) fn parse_qvalue(value: &str) -> Option<u16> {
+    let (integer, decimals) = value.split_once('.').unwrap_or((value, ""));
+    if decimals.len() > 3 || !decimals.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let thousandths = decimals
+        .bytes()
+        .chain(std::iter::repeat(b'0'))
+        .take(3)
+        .fold(0, |q, digit| q * 10 + u16::from(digit - b'0'));
+    let q = match integer {
+        "0" => thousandths,
+        "1" => 1000 + thousandths,
+        _ => return None,
+    };
+    (q <= 1000).then_some(q)
+}
+
+fn is_whitespace(c: char) -> bool {
+    c == ' ' || c == '\t'
+}
+
+/// See [RFC 9110](https://www.rfc-editor.org/rfc/rfc9110#name-tokens).
+fn is_token(s: &str) -> bool {
+    !s.is_empty()
+        && s.bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+/// Parse a quoted string (without its opening quote), returning its unescaped value and what comes after the closing quote.
+fn parse_quoted_string(input: &str) -> Option<(String, &str)> {
+    let mut value = String::new();
+    let mut chars = input.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((value, &input[i + 1..])),
+            '\\' => value.push(chars.next()?.1),
+            c => value.push(c),
+        }
+    }
+    None
+}
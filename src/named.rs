@@ -0,0 +1,119 @@
+use axum::body::Body;
+use axum::extract::FromRequest;
+use axum::http::HeaderValue;
+use axum::http::header::CONTENT_TYPE;
+use axum::response::{IntoResponse, Response};
+use prost::{Message, Name};
+
+use crate::config::ProtobufResponse;
+use crate::rejection;
+use crate::{
+    MediaType, PROTOBUF_CONTENT_TYPE, Protobuf, ProtobufConfig, ProtobufRejection, decode_request,
+};
+
+/// Protobuf Extractor / Response for messages that have a name.
+///
+/// This behaves exactly like [`Protobuf`], but uses the `messageType` parameter of the
+/// `Content-Type` header to tell which message a body holds, following Google's convention
+/// (e.g. `application/protobuf; messageType="pkg.Msg"`):
+/// - As a response, the parameter is set to the full name of the message.
+/// - As an extractor, the request is rejected with [`ProtobufRejection::MessageTypeMismatch`] if
+///   the parameter is present and doesn't match the full name of the expected message.
+///   Requests without the parameter are accepted.
+///
+/// This is a separate type because [`Protobuf`] only requires messages to implement
+/// [`prost::Message`]: requiring [`prost::Name`] too would break it for messages without a name
+/// (prost-build only implements it when enabled), and using it only for the messages that
+/// implement it would require specialization, which Rust doesn't support.
+///
+/// ```rust
+/// use axum_protobuf::NamedProtobuf;
+/// # #[derive(Clone, PartialEq, prost::Message)]
+/// # struct Order {}
+/// # impl prost::Name for Order {
+/// #     const NAME: &'static str = "Order";
+/// #     const PACKAGE: &'static str = "shop";
+/// # }
+///
+/// // Rejects requests with a `messageType` other than `shop.Order`
+/// async fn handler(NamedProtobuf(order): NamedProtobuf<Order>) -> NamedProtobuf<Order> {
+///     NamedProtobuf(order)
+/// }
+/// ```
+pub struct NamedProtobuf<T>(pub T);
+
+impl<T> IntoResponse for NamedProtobuf<T>
+where
+    T: Message + Name,
+{
+    fn into_response(self) -> Response {
+        let mut res = Protobuf(self.0).into_response();
+        // Encoding errors are not protobuf responses
+        if res.extensions().get::<ProtobufResponse>().is_some() {
+            let message_type = T::full_name();
+            let content_type = with_message_type(PROTOBUF_CONTENT_TYPE, &message_type);
+            res.headers_mut().insert(CONTENT_TYPE, content_type.clone());
+            res.extensions_mut().insert(ProtobufResponse {
+                message_type: Some(message_type),
+                content_type,
+            });
+        }
+        res
+    }
+}
+impl<S, T> FromRequest<S> for NamedProtobuf<T>
+where
+    T: Message + Name + Default,
+    S: Send + Sync,
+{
+    type Rejection = ProtobufRejection;
+
+    async fn from_request(
+        mut req: axum::http::Request<Body>,
+        _: &S,
+    ) -> Result<Self, Self::Rejection> {
+        decode_named_request(&mut req)
+            .await
+            .map(|x| Self(x))
+            .map_err(|rejection| rejection::handle(rejection, req.into_parts().0))
+    }
+}
+impl<T> From<NamedProtobuf<T>> for Protobuf<T> {
+    fn from(val: NamedProtobuf<T>) -> Self {
+        Protobuf(val.0)
+    }
+}
+impl<T> From<Protobuf<T>> for NamedProtobuf<T> {
+    fn from(val: Protobuf<T>) -> Self {
+        NamedProtobuf(val.0)
+    }
+}
+
+/// Decode a protobuf request body, checking the `messageType` parameter of its `content-type` header.
+async fn decode_named_request<T>(
+    req: &mut axum::http::Request<Body>,
+) -> Result<T, ProtobufRejection>
+where
+    T: Message + Name + Default,
+{
+    let config = ProtobufConfig::from_extensions(req.extensions());
+    let media_type = MediaType::from_content_type(req.headers())
+        .filter(|media_type| config.is_protobuf(media_type))
+        .ok_or(ProtobufRejection::MissingProtobufContentType)?;
+    if let Some(message_type) = media_type.param("messageType")
+        && message_type.trim_start_matches('.') != T::full_name()
+    {
+        return Err(ProtobufRejection::MessageTypeMismatch);
+    }
+
+    decode_request(req, None).await
+}
+
+/// Add a `messageType` parameter to a content type.
+///
+/// Falls back to the content type alone if the message type can't be used in a header.
+pub(crate) fn with_message_type(content_type: &str, message_type: &str) -> HeaderValue {
+    HeaderValue::try_from(format!("{content_type}; messageType=\"{message_type}\""))
+        .or_else(|_| HeaderValue::try_from(content_type))
+        .unwrap() // the content type comes from a valid header value
+}
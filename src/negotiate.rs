@@ -0,0 +1,318 @@
+use std::sync::{Arc, Mutex};
+
+use axum::body::Body;
+use axum::http::header::{ACCEPT, CONTENT_TYPE, VARY};
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use prost::Message;
+use serde::Serialize;
+
+use crate::media_type::parse_qvalue;
+use crate::protojson::{Encoder, JSON_CONTENT_TYPE};
+#[cfg(feature = "text-format")]
+use crate::text_format::{self, TEXT_FORMAT_CONTENT_TYPES};
+use crate::{
+    MediaType, PROTOBUF_CONTENT_TYPE, PROTOBUF_CONTENT_TYPES, ProtoJson, ProtobufConfig, WireFormat,
+};
+
+/// Negotiated Response.
+///
+/// This sends a message in the format requested by the `accept` header of the request, like
+/// [`ProtoJson::infer_response`], without having to pass the request headers around:
+///
+/// ```rust
+/// use axum::{Router, routing::get};
+/// use axum_protobuf::{Negotiate, ProtobufConfig};
+/// # #[derive(Clone, PartialEq, prost::Message, serde::Serialize)]
+/// # struct User {}
+///
+/// async fn handler() -> Negotiate<User> {
+///     Negotiate(User {})
+/// }
+///
+/// let app: Router = Router::new()
+///     .route("/user", get(handler))
+///     .layer(ProtobufConfig::new());
+/// ```
+///
+/// With the `text-format` feature, handlers can return `Negotiate<ProtoJsonText<T>>` to make the
+/// protobuf text format available as well. With the `proto3-json` feature,
+/// `Negotiate<Proto3Json<T>>` uses the canonical proto3 JSON mapping.
+///
+/// The format is chosen by the [`ProtobufConfig`](crate::ProtobufConfig) layer, which must be
+/// applied to the route: otherwise, the message is always sent in the protobuf binary format.
+/// The response is then compressed according to the configuration.
+///
+/// The status code, headers and extensions set by the handler are kept, as in
+/// `(StatusCode::CREATED, [(LOCATION, "/users/1")], Negotiate(user))`: the layer only replaces
+/// the body and its `content-type`.
+pub struct Negotiate<T>(pub T);
+
+impl<T> IntoResponse for Negotiate<T>
+where
+    T: Message + Serialize + 'static,
+{
+    fn into_response(self) -> Response {
+        Negotiation::response(self.0, ProtoJson::encoder())
+    }
+}
+
+type RespondFn =
+    Box<dyn FnOnce(&HeaderMap, &ProtobufConfig) -> Result<Response, NegotiationError> + Send>;
+
+/// Added to the extensions of negotiated responses, so that the [`ProtobufConfig`](crate::ProtobufConfig)
+/// layer can construct the actual response once it knows the request headers.
+#[derive(Clone)]
+pub(crate) struct Negotiation(Arc<Mutex<Option<RespondFn>>>);
+
+impl Negotiation {
+    /// Construct a negotiated response, whose actual content is constructed by the layer.
+    pub(crate) fn response<T>(message: T, encoder: Encoder<T>) -> Response
+    where
+        T: Message + 'static,
+    {
+        // Sent as is when the route has no `ProtobufConfig` layer
+        let protobuf = message.encode_to_vec();
+        let respond: RespondFn = Box::new(move |header_map, config| {
+            encoder.negotiate(message, header_map, Some(config.fallback_format), config)
+        });
+        Response::builder()
+            .header(CONTENT_TYPE, PROTOBUF_CONTENT_TYPE)
+            .extension(Negotiation(Arc::new(Mutex::new(Some(respond)))))
+            .body(Body::from(protobuf))
+            .unwrap() // we know this will be valid since we made it
+    }
+
+    /// Apply the format requested by the headers to the response of the handler.
+    ///
+    /// The status code, headers and extensions of the response are kept: only the body and the
+    /// headers describing it are replaced.
+    pub(crate) fn respond(
+        self,
+        res: Response,
+        header_map: &HeaderMap,
+        config: &ProtobufConfig,
+    ) -> Response {
+        let Some(respond) = self.0.lock().ok().and_then(|mut respond| respond.take()) else {
+            return Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(Body::from("Negotiated response was already sent"))
+                .unwrap(); // we know this will be valid since we made it
+        };
+        let (negotiated, body) = match respond(header_map, config) {
+            Ok(negotiated) => negotiated.into_parts(),
+            Err(e) => return e.into_response(),
+        };
+
+        let (mut parts, _) = res.into_parts();
+        for name in negotiated.headers.keys() {
+            if name != VARY {
+                parts.headers.remove(name);
+            }
+            for value in negotiated.headers.get_all(name) {
+                parts.headers.append(name, value.clone());
+            }
+        }
+        parts.extensions.extend(negotiated.extensions);
+        Response::from_parts(parts, body)
+    }
+}
+
+/// Format of the responses to requests without an `accept` header.
+///
+/// Many HTTP clients never send an `accept` header: instead of rejecting their requests, a
+/// fallback format is used for them. It defaults to [`FallbackFormat::SameAsRequest`], and can be
+/// chosen for a single response with [`ProtoJson::infer_response_or`], or for all the responses of
+/// a route with [`ProtobufConfig::fallback_format`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FallbackFormat {
+    /// Respond with JSON.
+    Json,
+    /// Respond with protobuf.
+    Protobuf,
+    /// Respond in the same format as the request body, according to its `content-type` header.
+    ///
+    /// Requests without a body in a supported format are still rejected.
+    SameAsRequest,
+}
+
+impl FallbackFormat {
+    /// The response format to use for a request.
+    pub(crate) fn resolve(
+        self,
+        header_map: &HeaderMap,
+        config: &ProtobufConfig,
+    ) -> Option<ResponseFormat> {
+        match self {
+            FallbackFormat::Json => Some(ResponseFormat::Json),
+            FallbackFormat::Protobuf => Some(ResponseFormat::Protobuf),
+            FallbackFormat::SameAsRequest => {
+                let media_type = MediaType::from_content_type(header_map)?;
+                Some(match WireFormat::from_media_type(&media_type, config)? {
+                    WireFormat::Json => ResponseFormat::Json,
+                    WireFormat::Protobuf => ResponseFormat::Protobuf,
+                    #[cfg(feature = "text-format")]
+                    WireFormat::TextFormat => {
+                        ResponseFormat::TextFormat(text_format::content_type(&media_type)?)
+                    }
+                })
+            }
+        }
+    }
+}
+
+/// Format of a response, chosen based on the `accept` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ResponseFormat {
+    Json,
+    Protobuf,
+    #[cfg(feature = "text-format")]
+    TextFormat(&'static str),
+}
+
+/// Content types of the response formats, by order of preference.
+const FORMATS: &[(&str, ResponseFormat)] = &[
+    (JSON_CONTENT_TYPE, ResponseFormat::Json),
+    (PROTOBUF_CONTENT_TYPES[0], ResponseFormat::Protobuf),
+    (PROTOBUF_CONTENT_TYPES[1], ResponseFormat::Protobuf),
+    (PROTOBUF_CONTENT_TYPES[2], ResponseFormat::Protobuf),
+    #[cfg(feature = "text-format")]
+    (
+        TEXT_FORMAT_CONTENT_TYPES[0],
+        ResponseFormat::TextFormat(TEXT_FORMAT_CONTENT_TYPES[0]),
+    ),
+    #[cfg(feature = "text-format")]
+    (
+        TEXT_FORMAT_CONTENT_TYPES[1],
+        ResponseFormat::TextFormat(TEXT_FORMAT_CONTENT_TYPES[1]),
+    ),
+];
+
+impl ResponseFormat {
+    /// The wire format of this response format.
+    fn wire_format(self) -> WireFormat {
+        match self {
+            ResponseFormat::Json => WireFormat::Json,
+            ResponseFormat::Protobuf => WireFormat::Protobuf,
+            #[cfg(feature = "text-format")]
+            ResponseFormat::TextFormat(_) => WireFormat::TextFormat,
+        }
+    }
+
+    /// Choose the best format for the `accept` header, among the available ones.
+    ///
+    /// Following RFC 9110, each format gets the q-value of the most specific media range that
+    /// matches it. The format with the highest q-value wins; ties are broken in favor of the
+    /// format of the request body, then using our order of preference.
+    /// Without an `accept` header, the fallback format is used if it is available.
+    pub(crate) fn negotiate(
+        header_map: &HeaderMap,
+        fallback: Option<FallbackFormat>,
+        config: &ProtobufConfig,
+        available: impl Fn(ResponseFormat) -> bool,
+    ) -> Result<Self, NegotiationError> {
+        let values = header_map.get_all(ACCEPT);
+        if values.iter().next().is_none() {
+            return fallback
+                .and_then(|fallback| fallback.resolve(header_map, config))
+                .filter(|format| available(*format))
+                .ok_or(NegotiationError::MissingAccept);
+        }
+        let ranges: Vec<(MediaType, u16)> = values
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|range| {
+                let range = MediaType::parse(range.trim())?;
+                // Ranges with an invalid q-value are ignored
+                let q = range.param("q").map_or(Some(1000), parse_qvalue)?;
+                Some((range, q))
+            })
+            .collect();
+        let request_format = WireFormat::from_headers(header_map, config);
+
+        FORMATS
+            .iter()
+            .filter(|(_, format)| available(*format))
+            .filter_map(|(content_type, format)| {
+                let (type_, subtype) = content_type.split_once('/')?;
+                let q = ranges
+                    .iter()
+                    .filter_map(|(range, q)| Some((range_specificity(range, type_, subtype)?, *q)))
+                    .max_by_key(|(specificity, _)| *specificity)
+                    .map(|(_, q)| q)?;
+                (q > 0).then_some((*format, q))
+            })
+            // `max_by` returns the last maximum, so iterate in reverse to favor our preferences
+            .rev()
+            .max_by(|(a, q_a), (b, q_b)| {
+                q_a.cmp(q_b).then_with(|| {
+                    (Some(a.wire_format()) == request_format)
+                        .cmp(&(Some(b.wire_format()) == request_format))
+                })
+            })
+            .map(|(format, _)| format)
+            .ok_or(NegotiationError::NotAcceptable)
+    }
+}
+
+/// Whether JSON is preferred to protobuf by the `accept` header, or by the fallback format.
+pub(crate) fn prefers_json(header_map: &HeaderMap, config: &ProtobufConfig) -> bool {
+    let fallback = Some(config.fallback_format);
+    let format = ResponseFormat::negotiate(header_map, fallback, config, |format| {
+        matches!(format, ResponseFormat::Json | ResponseFormat::Protobuf)
+    });
+    matches!(format, Ok(ResponseFormat::Json))
+}
+
+/// How specific a media range matching a media type is, or `None` if it doesn't match it.
+///
+/// Parameters (other than the q-value) are ignored for matching, but make a range more specific.
+fn range_specificity(range: &MediaType, type_: &str, subtype: &str) -> Option<usize> {
+    let params = range.params().filter(|(name, _)| *name != "q").count();
+    match (range.type_(), range.subtype()) {
+        ("*", "*") => Some(0),
+        (t, "*") if t == type_ => Some(1),
+        (t, s) if t == type_ && s == subtype => Some(2 + params),
+        _ => None,
+    }
+}
+
+/// Reasons why no response format could be chosen.
+pub(crate) enum NegotiationError {
+    /// The `accept` header is missing.
+    MissingAccept,
+    /// None of the formats is acceptable.
+    NotAcceptable,
+}
+impl IntoResponse for NegotiationError {
+    fn into_response(self) -> Response {
+        let (status, body) = match self {
+            NegotiationError::MissingAccept => (
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Missing '{ACCEPT}' header with value 'application/json' or 'application/protobuf'"
+                ),
+            ),
+            NegotiationError::NotAcceptable => (
+                StatusCode::NOT_ACCEPTABLE,
+                format!(
+                    "None of the media types of the '{ACCEPT}' header are available, use 'application/json' or 'application/protobuf'"
+                ),
+            ),
+        };
+        vary_accept(
+            Response::builder()
+                .status(status)
+                .body(Body::from(body))
+                .unwrap(), // we know this will be valid since we made it
+        )
+    }
+}
+
+/// Add `accept` to the `vary` header of a negotiated response.
+pub(crate) fn vary_accept(mut res: Response) -> Response {
+    res.headers_mut()
+        .append(VARY, HeaderValue::from_static("accept"));
+    res
+}
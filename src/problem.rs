@@ -0,0 +1,39 @@
+use axum::http::HeaderValue;
+use axum::response::Response;
+
+use crate::rejection::{RejectionDetails, replace_body};
+
+const PROBLEM_JSON_CONTENT_TYPE: &str = "application/problem+json";
+
+/// Replace the body of a rejection response with problem details, following
+/// [RFC 9457](https://www.rfc-editor.org/rfc/rfc9457).
+pub(crate) fn response(res: Response, details: &RejectionDetails) -> Response {
+    let mut problem = serde_json::json!({
+        "type": details.type_uri(),
+        "title": details.title,
+        "status": res.status().as_u16(),
+    });
+    if let Some(detail) = &details.detail {
+        problem["detail"] = detail.as_str().into();
+    }
+    if !details.field_violations.is_empty() {
+        problem["errors"] = details
+            .field_violations
+            .iter()
+            .map(|violation| {
+                let mut error = serde_json::json!({
+                    "detail": violation.description,
+                });
+                if !violation.field.is_empty() {
+                    error["field"] = violation.field.as_str().into();
+                }
+                error
+            })
+            .collect();
+    }
+    replace_body(
+        res,
+        problem.to_string().into_bytes(),
+        HeaderValue::from_static(PROBLEM_JSON_CONTENT_TYPE),
+    )
+}
@@ -0,0 +1,184 @@
+use axum::body::Body;
+use axum::extract::{FromRequest, OptionalFromRequest};
+use axum::http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use prost::Message;
+use prost_reflect::{DynamicMessage, ReflectMessage, SerializeOptions};
+use serde::de::Error;
+
+use crate::negotiate::Negotiation;
+use crate::protojson::{Decoder, Encoder, JSON_CONTENT_TYPE};
+use crate::{FallbackFormat, Negotiate, ProtoJsonRejection};
+
+/// ProtoJson Extractor using the canonical proto3 JSON mapping.
+///
+/// This works like [`ProtoJson`](crate::ProtoJson), but JSON request bodies and responses follow
+/// the [canonical proto3 JSON mapping](https://protobuf.dev/programming-guides/json/)
+/// (lowerCamelCase field names, 64-bit integers as strings, enum names, RFC 3339 timestamps, ...)
+/// instead of the [`serde`] implementation of messages, so that clients using the official
+/// protobuf libraries can understand it. Both lowerCamelCase and original field names are accepted.
+///
+/// Messages must implement `prost_reflect::ReflectMessage` (but not the [`serde`] traits), as
+/// their descriptor is needed to map them to JSON:
+///
+/// ```rust
+/// use axum::http::HeaderMap;
+/// use axum::response::Response;
+/// use axum_protobuf::Proto3Json;
+/// # #[derive(Clone, PartialEq, prost::Message)]
+/// # struct User {}
+/// # impl prost_reflect::ReflectMessage for User {
+/// #     fn descriptor(&self) -> prost_reflect::MessageDescriptor { unimplemented!() }
+/// # }
+///
+/// async fn handler(headers: HeaderMap, Proto3Json(user): Proto3Json<User>) -> Response {
+///     Proto3Json(user).infer_response(&headers)
+/// }
+/// ```
+///
+/// Handlers can also return `Negotiate<Proto3Json<T>>`, and
+/// [`EncodedProtobuf::with_proto3_json`](crate::EncodedProtobuf::with_proto3_json) pre-encodes a
+/// message with this mapping.
+///
+/// ⚠️ Since parsing the request body requires consuming it, the [`Proto3Json`] extractor must be
+/// *last* if there are multiple extractors in a handler.
+/// See ["the order of extractors"](https://docs.rs/axum/latest/axum/extract/index.html#the-order-of-extractors).
+pub struct Proto3Json<T>(pub T);
+
+impl<T> Proto3Json<T>
+where
+    T: Message + ReflectMessage,
+{
+    /// Attempt to construct a response based on the `accept` header.
+    ///
+    /// See [`ProtoJson::try_infer_response`](crate::ProtoJson::try_infer_response) for more details.
+    pub fn try_infer_response(self, header_map: &HeaderMap) -> Option<Response> {
+        Self::encoder()
+            .infer_response(self.0, header_map, None)
+            .ok()
+    }
+
+    /// Construct a response based on the `accept` header.
+    ///
+    /// See [`ProtoJson::infer_response`](crate::ProtoJson::infer_response) for more details.
+    pub fn infer_response(self, header_map: &HeaderMap) -> Response {
+        self.infer_response_or(header_map, FallbackFormat::SameAsRequest)
+    }
+
+    /// Construct a response based on the `accept` header, or in the fallback format if it is not set.
+    ///
+    /// See [`ProtoJson::infer_response_or`](crate::ProtoJson::infer_response_or) for more details.
+    pub fn infer_response_or(self, header_map: &HeaderMap, fallback: FallbackFormat) -> Response {
+        Self::encoder()
+            .infer_response(self.0, header_map, Some(fallback))
+            .unwrap_or_else(IntoResponse::into_response)
+    }
+
+    /// How the messages are sent: proto3 JSON or protobuf.
+    fn encoder() -> Encoder<T> {
+        Encoder {
+            json: response,
+            #[cfg(feature = "text-format")]
+            text_format: None,
+        }
+    }
+}
+
+impl<T> Proto3Json<T>
+where
+    T: Message + Default + ReflectMessage,
+{
+    /// How the request bodies are decoded: proto3 JSON or protobuf.
+    fn decoder() -> Decoder<T> {
+        Decoder {
+            json: |bytes| from_slice(bytes).map_err(ProtoJsonRejection::Proto3JsonError),
+            #[cfg(feature = "text-format")]
+            text_format: None,
+        }
+    }
+}
+
+impl<S, T> FromRequest<S> for Proto3Json<T>
+where
+    T: Message + Default + ReflectMessage,
+    S: Send + Sync,
+{
+    type Rejection = ProtoJsonRejection;
+
+    async fn from_request(req: axum::http::Request<Body>, _: &S) -> Result<Self, Self::Rejection> {
+        Self::decoder().decode(req).await.map(Self)
+    }
+}
+/// A missing `content-type` header or an empty body gives `None`.
+/// Other errors are still rejected.
+impl<S, T> OptionalFromRequest<S> for Proto3Json<T>
+where
+    T: Message + Default + ReflectMessage,
+    S: Send + Sync,
+{
+    type Rejection = ProtoJsonRejection;
+
+    async fn from_request(
+        req: axum::http::Request<Body>,
+        _: &S,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        Self::decoder()
+            .decode_optional(req)
+            .await
+            .map(|x| x.map(Self))
+    }
+}
+
+impl<T> IntoResponse for Negotiate<Proto3Json<T>>
+where
+    T: Message + ReflectMessage + 'static,
+{
+    fn into_response(self) -> Response {
+        Negotiation::response(self.0.0, Proto3Json::encoder())
+    }
+}
+
+/// Decode a message from its canonical proto3 JSON representation.
+///
+/// Both the lowerCamelCase and the original field names are accepted.
+fn from_slice<T>(bytes: &[u8]) -> Result<T, serde_json::Error>
+where
+    T: ReflectMessage + Default,
+{
+    let mut deserializer = serde_json::Deserializer::from_slice(bytes);
+    let message = DynamicMessage::deserialize(T::default().descriptor(), &mut deserializer)?;
+    deserializer.end()?;
+    message.transcode_to().map_err(serde_json::Error::custom)
+}
+
+/// Encode a message to its canonical proto3 JSON representation.
+pub(crate) fn to_vec<T>(message: &T) -> Result<Vec<u8>, serde_json::Error>
+where
+    T: ReflectMessage,
+{
+    let mut serializer = serde_json::Serializer::new(Vec::new());
+    message
+        .transcode_to_dynamic()
+        .serialize_with_options(&mut serializer, &SerializeOptions::new())?;
+    Ok(serializer.into_inner())
+}
+
+/// Construct a response with a message in its canonical proto3 JSON representation.
+fn response<T>(message: T) -> Response
+where
+    T: ReflectMessage,
+{
+    match to_vec(&message) {
+        Ok(buf) => Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, JSON_CONTENT_TYPE)
+            .header(CONTENT_LENGTH, buf.len())
+            .body(Body::from(buf))
+            .unwrap(), // we know this will be valid since we made it
+        Err(e) => Response::builder()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body(Body::from(e.to_string()))
+            .unwrap(), // we know this will be valid since we made it
+    }
+}
@@ -1,19 +1,39 @@
+use std::convert::Infallible;
+use std::fmt;
+
+#[cfg(feature = "text-format")]
+use axum::BoxError;
 use axum::Json;
 use axum::body::Body;
-use axum::extract::FromRequest;
 use axum::extract::rejection::JsonRejection;
-use axum::http::header::{ACCEPT, CONTENT_TYPE};
+use axum::extract::{self, FromRequest, OptionalFromRequestParts};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::request::Parts;
 use axum::http::{HeaderMap, StatusCode};
 use axum::response::{IntoResponse, Response};
 use prost::Message;
 use serde::Serialize;
 use serde::de::DeserializeOwned;
 
-use crate::{PROTOBUF_CONTENT_TYPES, Protobuf, ProtobufRejection};
+use crate::compression::compress_handler_response;
+use crate::negotiate::{FallbackFormat, NegotiationError, ResponseFormat, vary_accept};
+use crate::rejection::{self, HandledRejection, Rejection, RejectionDetails, rejection_response};
+#[cfg(feature = "text-format")]
+use crate::text_format;
+use crate::{
+    MediaType, Protobuf, ProtobufConfig, ProtobufRejection, buffer_json_body,
+    decode_optional_request, decode_request,
+};
 
-const JSON_CONTENT_TYPE: &str = "application/json";
+pub(crate) const JSON_CONTENT_TYPE: &str = "application/json";
 
 /// Possible reasons why a request could be rejected.
+///
+/// When a [`ProtobufConfig::rejection_handler`] is set, every rejection is wrapped in
+/// [`ProtoJsonRejection::Handled`]: matches on the other variants don't apply, and the original
+/// rejection is available with [`HandledRejection::rejection`].
+#[derive(Debug)]
+#[non_exhaustive]
 pub enum ProtoJsonRejection {
     /// Protobuf-related error.
     ProtobufRejection(ProtobufRejection),
@@ -23,19 +43,194 @@ pub enum ProtoJsonRejection {
 
     /// Content-Type header is missing or has an unsupported value.
     MissingContentType,
+
+    /// Parsing the protobuf text format failed.
+    #[cfg(feature = "text-format")]
+    TextFormatError(BoxError),
+
+    /// Decoding the canonical proto3 JSON representation failed.
+    #[cfg(feature = "proto3-json")]
+    Proto3JsonError(serde_json::Error),
+
+    /// The request was rejected, and the response is constructed by the rejection handler set with
+    /// [`ProtobufConfig::rejection_handler`].
+    Handled(HandledRejection),
+}
+impl ProtoJsonRejection {
+    /// Get the status code used for the response of this rejection.
+    ///
+    /// For [`Handled`](Self::Handled) rejections, this is the status code of the original
+    /// rejection: the response is constructed by the rejection handler.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ProtoJsonRejection::ProtobufRejection(rejection) => rejection.status(),
+            ProtoJsonRejection::JsonRejection(rejection) => rejection.status(),
+            ProtoJsonRejection::MissingContentType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            #[cfg(feature = "text-format")]
+            ProtoJsonRejection::TextFormatError(_) => StatusCode::BAD_REQUEST,
+            // Same status codes as axum's `JsonRejection`
+            #[cfg(feature = "proto3-json")]
+            ProtoJsonRejection::Proto3JsonError(e) if e.is_data() => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+            #[cfg(feature = "proto3-json")]
+            ProtoJsonRejection::Proto3JsonError(_) => StatusCode::BAD_REQUEST,
+            ProtoJsonRejection::Handled(rejection) => rejection.rejection().status(),
+        }
+    }
+
+    pub(crate) fn details(&self) -> RejectionDetails {
+        let variant = |name| ("ProtoJsonRejection", name);
+        match self {
+            ProtoJsonRejection::ProtobufRejection(rejection) => rejection.details(),
+            ProtoJsonRejection::JsonRejection(rejection) => RejectionDetails::new(
+                variant("JsonRejection"),
+                "Failed to decode the JSON request body",
+                self.body_text(),
+            )
+            .with_detail(rejection.body_text()),
+            ProtoJsonRejection::MissingContentType => RejectionDetails::new(
+                variant("MissingContentType"),
+                "Missing 'content-type' header",
+                self.body_text(),
+            ),
+            #[cfg(feature = "text-format")]
+            ProtoJsonRejection::TextFormatError(e) => RejectionDetails::new(
+                variant("TextFormatError"),
+                "Protobuf text format parsing error",
+                self.body_text(),
+            )
+            .with_detail(e),
+            #[cfg(feature = "proto3-json")]
+            ProtoJsonRejection::Proto3JsonError(e) => RejectionDetails::new(
+                variant("Proto3JsonError"),
+                "Failed to decode the JSON request body",
+                self.body_text(),
+            )
+            .with_detail(e),
+            ProtoJsonRejection::Handled(rejection) => rejection.rejection().details(),
+        }
+    }
+
+    /// Get the body text used for the response of this rejection.
+    ///
+    /// For [`Handled`](Self::Handled) rejections, this is the body text of the original
+    /// rejection: the response is constructed by the rejection handler.
+    pub fn body_text(&self) -> String {
+        match self {
+            ProtoJsonRejection::ProtobufRejection(rejection) => rejection.body_text(),
+            ProtoJsonRejection::JsonRejection(rejection) => rejection.body_text(),
+            ProtoJsonRejection::MissingContentType => "Missing 'content-type' header that has the value 'application/json' or 'application/protobuf'".to_owned(),
+            #[cfg(feature = "text-format")]
+            ProtoJsonRejection::TextFormatError(_) => "Protobuf text format parsing error".to_owned(),
+            // Same messages as axum's `JsonRejection`
+            #[cfg(feature = "proto3-json")]
+            ProtoJsonRejection::Proto3JsonError(e) if e.is_data() => {
+                format!("Failed to deserialize the JSON body into the target type: {e}")
+            }
+            #[cfg(feature = "proto3-json")]
+            ProtoJsonRejection::Proto3JsonError(e) => {
+                format!("Failed to parse the request body as JSON: {e}")
+            }
+            ProtoJsonRejection::Handled(rejection) => rejection.rejection().body_text(),
+        }
+    }
 }
 impl IntoResponse for ProtoJsonRejection {
     fn into_response(self) -> Response {
         match self {
-            ProtoJsonRejection::JsonRejection(rejection) => rejection.into_response(),
-            ProtoJsonRejection::ProtobufRejection(rejection) => rejection.into_response(),
-            ProtoJsonRejection::MissingContentType => {
-                Response::builder()
-                    .status(StatusCode::UNSUPPORTED_MEDIA_TYPE)
-                    .body(Body::from("Missing 'content-type' header that has the value 'application/json' or 'application/protobuf'"))
-                    .unwrap() // we know this will be valid since we made it
-            }
+            ProtoJsonRejection::Handled(rejection) => rejection.into_response(),
+            _ => rejection_response(Rejection::ProtoJson(self)),
+        }
+    }
+}
+impl fmt::Display for ProtoJsonRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.body_text())
+    }
+}
+impl std::error::Error for ProtoJsonRejection {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ProtoJsonRejection::ProtobufRejection(rejection) => Some(rejection),
+            ProtoJsonRejection::JsonRejection(rejection) => Some(rejection),
+            ProtoJsonRejection::MissingContentType => None,
+            #[cfg(feature = "text-format")]
+            ProtoJsonRejection::TextFormatError(e) => Some(e.as_ref()),
+            #[cfg(feature = "proto3-json")]
+            ProtoJsonRejection::Proto3JsonError(e) => Some(e),
+            ProtoJsonRejection::Handled(rejection) => Some(rejection.rejection()),
+        }
+    }
+}
+impl From<HandledRejection> for ProtoJsonRejection {
+    fn from(rejection: HandledRejection) -> Self {
+        ProtoJsonRejection::Handled(rejection)
+    }
+}
+
+/// Format of the body of a request, detected from its `content-type` header.
+///
+/// It can be used as an `Option<WireFormat>` extractor, to know which format a [`ProtoJson`]
+/// extractor decoded (`None` means that the request has no body in a supported format):
+///
+/// ```rust
+/// use axum_protobuf::{ProtoJson, WireFormat};
+/// # #[derive(Clone, PartialEq, prost::Message, serde::Deserialize)]
+/// # struct Event {}
+///
+/// async fn handler(format: Option<WireFormat>, ProtoJson(event): ProtoJson<Event>) {
+///     if format == Some(WireFormat::Json) {
+///         // ...
+///     }
+/// }
+/// ```
+///
+/// Negotiated responses (such as [`ProtoJson::infer_response`] and [`Negotiate`](crate::Negotiate))
+/// mirror the format of the request: it is used when the `accept` header is missing, and preferred
+/// when several formats are equally acceptable (as with `accept: */*`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum WireFormat {
+    /// JSON (`application/json` or a `+json` media type).
+    Json,
+    /// Protobuf (one of the content types of the [`ProtobufConfig`]).
+    Protobuf,
+    /// Protobuf text format (`text/x-protobuf` or `application/x-protobuf-text`).
+    #[cfg(feature = "text-format")]
+    TextFormat,
+}
+
+impl WireFormat {
+    /// Detect the format of a request from its `content-type` header.
+    pub(crate) fn from_headers(header_map: &HeaderMap, config: &ProtobufConfig) -> Option<Self> {
+        WireFormat::from_media_type(&MediaType::from_content_type(header_map)?, config)
+    }
+
+    /// Detect the format designated by a media type.
+    pub(crate) fn from_media_type(media_type: &MediaType, config: &ProtobufConfig) -> Option<Self> {
+        if media_type.is_json() {
+            return Some(WireFormat::Json);
+        }
+        #[cfg(feature = "text-format")]
+        if text_format::content_type(media_type).is_some() {
+            return Some(WireFormat::TextFormat);
         }
+        config
+            .is_protobuf(media_type)
+            .then_some(WireFormat::Protobuf)
+    }
+}
+
+impl<S> OptionalFromRequestParts<S> for WireFormat
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Option<Self>, Self::Rejection> {
+        let config = ProtobufConfig::from_extensions(&parts.extensions);
+        Ok(WireFormat::from_headers(&parts.headers, config))
     }
 }
 
@@ -43,12 +238,19 @@ impl IntoResponse for ProtoJsonRejection {
 ///
 /// This can decode request bodies into some type that implements ([`prost::Message`] and [`Default`]) or [`serde::Deserialize`].
 ///
+/// The request will be rejected (and a [`ProtoJsonRejection`] will be returned) if:
 /// - The request doesn't have a `Content-Type: application/protobuf` / `Content-Type: application/json` (or similar) header.
 /// - The request body failed to decode into the expected protobuf type.
 /// - The body doesn't contain syntactically valid JSON.
 /// - The body contains syntactically valid JSON, but it couldn't be deserialized into the target type.
 /// - Buffering the request body fails.
 ///
+/// With the `text-format` feature, the `ProtoJsonText` extractor also accepts request bodies in
+/// the protobuf text format.
+///
+/// JSON uses the [`serde`] implementation of messages. With the `proto3-json` feature, the
+/// `Proto3Json` extractor uses the canonical proto3 JSON mapping instead.
+///
 /// ⚠️ Since parsing Protobuf and JSON requires consuming the request body, the [`ProtoJson`] extractor must be
 /// *last* if there are multiple extractors in a handler.
 /// See ["the order of extractors"](https://docs.rs/axum/latest/axum/extract/index.html#the-order-of-extractors).
@@ -56,35 +258,278 @@ pub struct ProtoJson<T>(pub T);
 
 impl<T> ProtoJson<T>
 where
-    T: Message + Default + Serialize,
+    T: Message + Serialize,
 {
     /// Attempt to construct a response based on the `accept` header.
+    ///
+    /// The format is negotiated following [RFC 9110](https://www.rfc-editor.org/rfc/rfc9110#name-accept):
+    /// media ranges with wildcards and q-values are supported, and the `vary: accept` header is added.
+    /// When several formats are equally acceptable, the format of the request body is preferred.
+    /// Returns `None` if the `accept` header is not set, or if none of the formats is acceptable.
+    ///
+    /// When one of the `gzip`, `deflate`, `brotli` or `zstd` features is enabled, the response is
+    /// also compressed based on the `accept-encoding` header (if it is larger than 1024 bytes).
+    /// When the [`ProtobufConfig`] layer is applied, its compression settings are used instead.
     pub fn try_infer_response(self, header_map: &HeaderMap) -> Option<Response> {
-        let accept = header_map.get(ACCEPT).and_then(|v| v.to_str().ok());
-
-        match accept {
-            Some(JSON_CONTENT_TYPE) => Some(Json(self.0).into_response()),
-            Some(content_type) if PROTOBUF_CONTENT_TYPES.contains(&content_type) => {
-                Some(Protobuf(self.0).into_response())
-            }
-            _ => None,
-        }
+        Self::encoder()
+            .infer_response(self.0, header_map, None)
+            .ok()
     }
 
     /// Construct a response based on the `accept` header.
     ///
-    /// If the `accept` header is not set or is not recognized, a [`StatusCode::BAD_REQUEST`] response is returned.
+    /// If the `accept` header is not set, the response mirrors the format of the request body
+    /// ([`FallbackFormat::SameAsRequest`]), and a [`StatusCode::BAD_REQUEST`] response is returned
+    /// if the request has no body in a supported format.
+    /// If none of the formats is acceptable, a [`StatusCode::NOT_ACCEPTABLE`] response is returned.
+    /// See [`ProtoJson::try_infer_response`] for more details.
     pub fn infer_response(self, header_map: &HeaderMap) -> Response {
-        self.try_infer_response(header_map).unwrap_or_else(
-            || {
-                Response::builder()
-                    .status(StatusCode::BAD_REQUEST)
-                    .body(Body::from(format!("Missing '{ACCEPT}' header with value 'application/json' or 'application/protobuf'")))
-                    .unwrap()
-            }, // we know this will be valid since we made it
-        )
+        self.infer_response_or(header_map, FallbackFormat::SameAsRequest)
+    }
+
+    /// Construct a response based on the `accept` header, or in the fallback format if it is not set.
+    ///
+    /// ```rust
+    /// use axum::http::HeaderMap;
+    /// use axum::response::Response;
+    /// use axum_protobuf::{FallbackFormat, ProtoJson};
+    /// # #[derive(Clone, PartialEq, prost::Message, serde::Serialize, serde::Deserialize)]
+    /// # struct User {}
+    ///
+    /// async fn handler(headers: HeaderMap, ProtoJson(user): ProtoJson<User>) -> Response {
+    ///     ProtoJson(user).infer_response_or(&headers, FallbackFormat::Json)
+    /// }
+    /// ```
+    ///
+    /// See [`ProtoJson::infer_response`] for more details.
+    pub fn infer_response_or(self, header_map: &HeaderMap, fallback: FallbackFormat) -> Response {
+        Self::encoder()
+            .infer_response(self.0, header_map, Some(fallback))
+            .unwrap_or_else(IntoResponse::into_response)
+    }
+
+    /// How the messages are sent: JSON or protobuf.
+    pub(crate) fn encoder() -> Encoder<T> {
+        Encoder {
+            json: json_response,
+            #[cfg(feature = "text-format")]
+            text_format: None,
+        }
+    }
+}
+
+impl<T> ProtoJson<T>
+where
+    T: Message + Default + DeserializeOwned,
+{
+    /// How the request bodies are decoded: JSON or protobuf.
+    fn decoder() -> Decoder<T> {
+        Decoder {
+            json: json_from_bytes,
+            #[cfg(feature = "text-format")]
+            text_format: None,
+        }
     }
 }
+
+/// Construct a JSON response with the [`serde`] implementation of a message.
+pub(crate) fn json_response<T>(message: T) -> Response
+where
+    T: Serialize,
+{
+    Json(message).into_response()
+}
+
+/// Decode a JSON request body with the [`serde`] implementation of a message.
+pub(crate) fn json_from_bytes<T>(bytes: &[u8]) -> Result<T, ProtoJsonRejection>
+where
+    T: DeserializeOwned,
+{
+    Json::<T>::from_bytes(bytes)
+        .map(|Json(x)| x)
+        .map_err(ProtoJsonRejection::JsonRejection)
+}
+
+/// How the responses of [`ProtoJson`] and similar types are constructed.
+///
+/// The formats that depend on more bounds than [`Message`] are encoded with function pointers, so
+/// that the same implementation serves all these types.
+pub(crate) struct Encoder<T> {
+    /// Construct a JSON response.
+    pub(crate) json: fn(T) -> Response,
+    /// Construct a response in the protobuf text format (with its content type), if available.
+    #[cfg(feature = "text-format")]
+    pub(crate) text_format: Option<fn(&T, &'static str) -> Response>,
+}
+
+impl<T> Clone for Encoder<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T> Copy for Encoder<T> {}
+
+impl<T> Encoder<T>
+where
+    T: Message,
+{
+    /// Whether a response format is available.
+    pub(crate) fn available(&self, format: ResponseFormat) -> bool {
+        match format {
+            ResponseFormat::Json | ResponseFormat::Protobuf => true,
+            #[cfg(feature = "text-format")]
+            ResponseFormat::TextFormat(_) => self.text_format.is_some(),
+        }
+    }
+
+    /// Construct a response in the format negotiated with the request headers, without compressing it.
+    pub(crate) fn negotiate(
+        &self,
+        message: T,
+        header_map: &HeaderMap,
+        fallback: Option<FallbackFormat>,
+        config: &ProtobufConfig,
+    ) -> Result<Response, NegotiationError> {
+        let format = ResponseFormat::negotiate(header_map, fallback, config, |format| {
+            self.available(format)
+        })?;
+        let res = match format {
+            ResponseFormat::Json => (self.json)(message),
+            ResponseFormat::Protobuf => Protobuf(message).into_response(),
+            #[cfg(feature = "text-format")]
+            ResponseFormat::TextFormat(content_type) => match self.text_format {
+                Some(text_format) => text_format(&message, content_type),
+                None => return Err(NegotiationError::NotAcceptable),
+            },
+        };
+        Ok(vary_accept(res))
+    }
+
+    /// Construct the response of a handler in the format negotiated with the request headers, and
+    /// compress it.
+    pub(crate) fn infer_response(
+        &self,
+        message: T,
+        header_map: &HeaderMap,
+        fallback: Option<FallbackFormat>,
+    ) -> Result<Response, NegotiationError> {
+        let res = self.negotiate(message, header_map, fallback, &ProtobufConfig::new())?;
+        Ok(compress_handler_response(res, header_map))
+    }
+}
+
+/// Decode a message from a JSON body.
+type FromJsonFn<T> = fn(&[u8]) -> Result<T, ProtoJsonRejection>;
+
+/// Decode a message from a body in the protobuf text format.
+#[cfg(feature = "text-format")]
+pub(crate) type FromTextFn<T> = fn(&str) -> Result<T, ProtoJsonRejection>;
+
+/// How the request bodies of [`ProtoJson`] and similar extractors are decoded.
+///
+/// Like [`Encoder`], the formats that depend on more bounds than [`Message`] are decoded with
+/// function pointers.
+pub(crate) struct Decoder<T> {
+    /// Decode a JSON body.
+    pub(crate) json: FromJsonFn<T>,
+    /// Decode a body in the protobuf text format, if supported.
+    #[cfg(feature = "text-format")]
+    pub(crate) text_format: Option<FromTextFn<T>>,
+}
+
+impl<T> Decoder<T>
+where
+    T: Message + Default,
+{
+    /// Decode a request body in the format given by its `content-type` header.
+    pub(crate) async fn decode(
+        &self,
+        mut req: axum::http::Request<Body>,
+    ) -> Result<T, ProtoJsonRejection> {
+        self.decode_body(&mut req)
+            .await
+            .map_err(|rejection| rejection::handle(rejection, req.into_parts().0))
+    }
+
+    /// Decode an optional request body in the format given by its `content-type` header.
+    ///
+    /// A missing `content-type` header or an empty body gives `None`.
+    pub(crate) async fn decode_optional(
+        &self,
+        mut req: axum::http::Request<Body>,
+    ) -> Result<Option<T>, ProtoJsonRejection> {
+        self.decode_optional_body(&mut req)
+            .await
+            .map_err(|rejection| rejection::handle(rejection, req.into_parts().0))
+    }
+
+    async fn decode_body(
+        &self,
+        req: &mut axum::http::Request<Body>,
+    ) -> Result<T, ProtoJsonRejection> {
+        let config = ProtobufConfig::from_extensions(req.extensions());
+
+        match WireFormat::from_headers(req.headers(), config) {
+            Some(WireFormat::Json) => {
+                let bytes = buffer_json_body(req)
+                    .await
+                    .map_err(ProtoJsonRejection::JsonRejection)?;
+                (self.json)(&bytes)
+            }
+            #[cfg(feature = "text-format")]
+            Some(WireFormat::TextFormat) => {
+                let Some(from_text) = self.text_format else {
+                    return Err(ProtoJsonRejection::MissingContentType);
+                };
+                let reject_empty_body = config.reject_empty_body;
+                match text_format::decode_request(req, from_text).await? {
+                    Some(x) => Ok(x),
+                    None if reject_empty_body => Err(ProtoJsonRejection::ProtobufRejection(
+                        ProtobufRejection::EmptyBody,
+                    )),
+                    None => Ok(T::default()),
+                }
+            }
+            Some(WireFormat::Protobuf) => decode_request(req, None)
+                .await
+                .map_err(ProtoJsonRejection::ProtobufRejection),
+            _ => Err(ProtoJsonRejection::MissingContentType),
+        }
+    }
+
+    async fn decode_optional_body(
+        &self,
+        req: &mut axum::http::Request<Body>,
+    ) -> Result<Option<T>, ProtoJsonRejection> {
+        if !req.headers().contains_key(CONTENT_TYPE) {
+            return Ok(None);
+        }
+
+        let config = ProtobufConfig::from_extensions(req.extensions());
+
+        match WireFormat::from_headers(req.headers(), config) {
+            Some(WireFormat::Json) => {
+                let bytes = buffer_json_body(req)
+                    .await
+                    .map_err(ProtoJsonRejection::JsonRejection)?;
+                if bytes.is_empty() {
+                    return Ok(None);
+                }
+                (self.json)(&bytes).map(Some)
+            }
+            #[cfg(feature = "text-format")]
+            Some(WireFormat::TextFormat) => match self.text_format {
+                Some(from_text) => text_format::decode_request(req, from_text).await,
+                None => Err(ProtoJsonRejection::MissingContentType),
+            },
+            Some(WireFormat::Protobuf) => decode_optional_request(req)
+                .await
+                .map_err(ProtoJsonRejection::ProtobufRejection),
+            _ => Err(ProtoJsonRejection::MissingContentType),
+        }
+    }
+}
+
 impl<T> From<Json<T>> for ProtoJson<T> {
     fn from(x: Json<T>) -> ProtoJson<T> {
         ProtoJson(x.0)
@@ -105,7 +550,7 @@ impl<T> From<Protobuf<T>> for ProtoJson<T> {
 }
 impl<T> From<ProtoJson<T>> for Protobuf<T>
 where
-    T: Message + Default,
+    T: Message,
 {
     fn from(val: ProtoJson<T>) -> Self {
         Protobuf(val.0)
@@ -119,27 +564,26 @@ where
 {
     type Rejection = ProtoJsonRejection;
 
+    async fn from_request(req: axum::http::Request<Body>, _: &S) -> Result<Self, Self::Rejection> {
+        Self::decoder().decode(req).await.map(Self)
+    }
+}
+/// A missing `content-type` header or an empty body gives `None`.
+/// Other errors are still rejected.
+impl<S, T> extract::OptionalFromRequest<S> for ProtoJson<T>
+where
+    T: Message + Default + DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ProtoJsonRejection;
+
     async fn from_request(
         req: axum::http::Request<Body>,
-        state: &S,
-    ) -> Result<Self, Self::Rejection> {
-        let request_type = req
-            .headers()
-            .get(CONTENT_TYPE)
-            .and_then(|value| value.to_str().ok());
-
-        match request_type {
-            Some(JSON_CONTENT_TYPE) => Json::<T>::from_request(req, state)
-                .await
-                .map(|x| x.into())
-                .map_err(ProtoJsonRejection::JsonRejection),
-            Some(content_type) if PROTOBUF_CONTENT_TYPES.contains(&content_type) => {
-                Protobuf::<T>::from_request(req, state)
-                    .await
-                    .map(|x| x.into())
-                    .map_err(ProtoJsonRejection::ProtobufRejection)
-            }
-            _ => Err(ProtoJsonRejection::MissingContentType),
-        }
+        _: &S,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        Self::decoder()
+            .decode_optional(req)
+            .await
+            .map(|x| x.map(Self))
     }
 }
@@ -0,0 +1,70 @@
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use base64::Engine;
+use base64::alphabet::URL_SAFE;
+use base64::engine::DecodePaddingMode;
+use base64::engine::general_purpose::{GeneralPurpose, GeneralPurposeConfig};
+use prost::Message;
+
+use crate::rejection;
+use crate::{ProtobufConfig, ProtobufRejection};
+
+const BASE64_URL: GeneralPurpose = GeneralPurpose::new(
+    &URL_SAFE,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// Protobuf Query Extractor.
+///
+/// This decodes a protobuf message from a query parameter of the request URI, which makes it
+/// possible to use protobuf inputs with (cacheable) `GET` requests.
+/// The message must be encoded in [base64url](https://datatracker.ietf.org/doc/html/rfc4648#section-5),
+/// with or without padding.
+///
+/// The query parameter is `message` by default: see [`ProtobufConfig::query_param`] to change it.
+///
+/// The request will be rejected (and a [`ProtobufRejection`] will be returned) if:
+/// - The query parameter is missing.
+/// - The query parameter is not valid base64url.
+/// - The decoded query parameter failed to decode into the expected protobuf type.
+///
+/// Since it doesn't consume the request body, this extractor can be used anywhere in the list of extractors.
+pub struct ProtobufQuery<T>(pub T);
+
+impl<S, T> FromRequestParts<S> for ProtobufQuery<T>
+where
+    T: Message + Default,
+    S: Send + Sync,
+{
+    type Rejection = ProtobufRejection;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        decode_query(parts)
+            .map(|x| Self(x))
+            .map_err(|rejection| rejection::handle_parts(rejection, parts))
+    }
+}
+
+/// Decode the message from the query parameter of a request.
+fn decode_query<T>(parts: &Parts) -> Result<T, ProtobufRejection>
+where
+    T: Message + Default,
+{
+    let name = &ProtobufConfig::from_extensions(&parts.extensions).query_param;
+
+    let value = parts
+        .uri
+        .query()
+        .and_then(|query| {
+            form_urlencoded::parse(query.as_bytes())
+                .find(|(key, _)| key == name.as_ref())
+                .map(|(_, value)| value)
+        })
+        .ok_or(ProtobufRejection::MissingQueryParameter)?;
+
+    let buf = BASE64_URL
+        .decode(value.as_bytes())
+        .map_err(|_| ProtobufRejection::InvalidBase64QueryParameter)?;
+
+    T::decode(buf.as_slice()).map_err(ProtobufRejection::ProtobufDecodeError)
+}
@@ -0,0 +1,353 @@
+use std::fmt;
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use axum::http::request::Parts;
+use axum::http::{HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use prost::DecodeError;
+
+#[cfg(feature = "serde")]
+use crate::ProtoJsonRejection;
+use crate::{ProtobufConfig, ProtobufRejection};
+
+/// A rejection of one of the extractors of this crate.
+///
+/// It is passed to the rejection handler set with
+/// [`ProtobufConfig::rejection_handler`](crate::ProtobufConfig::rejection_handler).
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Rejection {
+    /// Rejection of the [`Protobuf`](crate::Protobuf) extractors.
+    Protobuf(ProtobufRejection),
+    /// Rejection of the [`ProtoJson`](crate::ProtoJson) extractor.
+    #[cfg(feature = "serde")]
+    ProtoJson(ProtoJsonRejection),
+}
+
+impl Rejection {
+    /// Get the status code used for the response of this rejection.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Rejection::Protobuf(rejection) => rejection.status(),
+            #[cfg(feature = "serde")]
+            Rejection::ProtoJson(rejection) => rejection.status(),
+        }
+    }
+
+    /// Get the body text used for the response of this rejection.
+    pub fn body_text(&self) -> String {
+        match self {
+            Rejection::Protobuf(rejection) => rejection.body_text(),
+            #[cfg(feature = "serde")]
+            Rejection::ProtoJson(rejection) => rejection.body_text(),
+        }
+    }
+
+    pub(crate) fn details(&self) -> RejectionDetails {
+        match self {
+            Rejection::Protobuf(rejection) => rejection.details(),
+            #[cfg(feature = "serde")]
+            Rejection::ProtoJson(rejection) => rejection.details(),
+        }
+    }
+}
+impl IntoResponse for Rejection {
+    fn into_response(self) -> Response {
+        match self {
+            Rejection::Protobuf(rejection) => rejection.into_response(),
+            #[cfg(feature = "serde")]
+            Rejection::ProtoJson(rejection) => rejection.into_response(),
+        }
+    }
+}
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rejection::Protobuf(rejection) => rejection.fmt(f),
+            #[cfg(feature = "serde")]
+            Rejection::ProtoJson(rejection) => rejection.fmt(f),
+        }
+    }
+}
+impl std::error::Error for Rejection {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Rejection::Protobuf(rejection) => rejection.source(),
+            #[cfg(feature = "serde")]
+            Rejection::ProtoJson(rejection) => rejection.source(),
+        }
+    }
+}
+impl From<ProtobufRejection> for Rejection {
+    fn from(rejection: ProtobufRejection) -> Self {
+        Rejection::Protobuf(rejection)
+    }
+}
+#[cfg(feature = "serde")]
+impl From<ProtoJsonRejection> for Rejection {
+    fn from(rejection: ProtoJsonRejection) -> Self {
+        Rejection::ProtoJson(rejection)
+    }
+}
+
+type HandlerFn = dyn Fn(Rejection, &Parts) -> Response + Send + Sync;
+
+/// Function that constructs the responses of rejections.
+#[derive(Clone)]
+pub(crate) struct RejectionHandler(pub(crate) Arc<HandlerFn>);
+
+impl fmt::Debug for RejectionHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RejectionHandler")
+    }
+}
+
+/// A rejection of an extractor whose response is constructed by the rejection handler set with
+/// [`ProtobufConfig::rejection_handler`](crate::ProtobufConfig::rejection_handler).
+///
+/// The extractors keep the parts of the request when they reject it, and the handler is called
+/// when the rejection is converted into a response.
+pub struct HandledRejection(Box<Handled>);
+
+struct Handled {
+    rejection: Rejection,
+    request: Parts,
+    handler: RejectionHandler,
+}
+
+impl HandledRejection {
+    /// Get the rejection passed to the handler.
+    pub fn rejection(&self) -> &Rejection {
+        &self.0.rejection
+    }
+
+    /// Get the rejection without passing it to the handler.
+    pub fn into_rejection(self) -> Rejection {
+        self.0.rejection
+    }
+}
+impl IntoResponse for HandledRejection {
+    fn into_response(self) -> Response {
+        let Handled {
+            rejection,
+            request,
+            handler: RejectionHandler(handler),
+        } = *self.0;
+        handler(rejection, &request)
+    }
+}
+impl fmt::Debug for HandledRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HandledRejection")
+            .field("rejection", &self.0.rejection)
+            .field("request", &self.0.request)
+            .finish_non_exhaustive()
+    }
+}
+impl fmt::Display for HandledRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.rejection.fmt(f)
+    }
+}
+
+/// Pass a rejection of an extractor to the rejection handler of the configuration, if any.
+pub(crate) fn handle<R>(rejection: R, request: Parts) -> R
+where
+    R: Into<Rejection> + From<HandledRejection>,
+{
+    match ProtobufConfig::from_extensions(&request.extensions)
+        .rejection_handler
+        .clone()
+    {
+        Some(handler) => R::from(HandledRejection(Box::new(Handled {
+            rejection: rejection.into(),
+            request,
+            handler,
+        }))),
+        None => rejection,
+    }
+}
+
+/// Like [`handle`], for extractors that don't own the request: its parts are only copied when
+/// there is a rejection handler.
+pub(crate) fn handle_parts<R>(rejection: R, parts: &Parts) -> R
+where
+    R: Into<Rejection> + From<HandledRejection>,
+{
+    if ProtobufConfig::from_extensions(&parts.extensions)
+        .rejection_handler
+        .is_none()
+    {
+        return rejection;
+    }
+
+    let mut request = axum::http::Request::new(()).into_parts().0;
+    request.method = parts.method.clone();
+    request.uri = parts.uri.clone();
+    request.version = parts.version;
+    request.headers = parts.headers.clone();
+    request.extensions = parts.extensions.clone();
+    handle(rejection, request)
+}
+
+/// Construct the default response of a rejection.
+pub(crate) fn rejection_response(rejection: Rejection) -> Response {
+    Response::builder()
+        .status(rejection.status())
+        .extension(rejection.details())
+        .body(Body::from(rejection.body_text()))
+        .unwrap() // we know this will be valid since we made it
+}
+
+/// Details of a rejection, added to the extensions of rejection responses so that the
+/// [`ProtobufConfig`](crate::ProtobufConfig) layer can render them in other formats.
+#[derive(Debug, Clone)]
+pub(crate) struct RejectionDetails {
+    /// The rejection enum and its variant, such as `("ProtobufRejection", "EmptyBody")`.
+    #[cfg_attr(not(feature = "serde"), allow(dead_code))] // only used by problem details
+    pub(crate) variant: (&'static str, &'static str),
+    /// A short summary of the rejection, which doesn't depend on the request.
+    #[cfg_attr(not(feature = "serde"), allow(dead_code))] // only used by problem details
+    pub(crate) title: &'static str,
+    /// The body text of the rejection.
+    pub(crate) message: String,
+    /// The error that caused the rejection, if any.
+    pub(crate) detail: Option<String>,
+    /// The fields of the request message that could not be decoded.
+    pub(crate) field_violations: Vec<FieldViolation>,
+    /// The wire type of the field that could not be decoded and the expected one, if they don't match.
+    pub(crate) wire_type_mismatch: Option<(String, String)>,
+}
+
+/// A field of the request message that could not be decoded.
+#[derive(Debug, Clone)]
+pub(crate) struct FieldViolation {
+    /// Path of the field, such as `items.name` (empty without decode diagnostics).
+    pub(crate) field: String,
+    /// Why the field could not be decoded.
+    pub(crate) description: String,
+}
+
+impl RejectionDetails {
+    pub(crate) fn new(
+        variant: (&'static str, &'static str),
+        title: &'static str,
+        message: String,
+    ) -> Self {
+        RejectionDetails {
+            variant,
+            title,
+            message,
+            detail: None,
+            field_violations: Vec::new(),
+            wire_type_mismatch: None,
+        }
+    }
+
+    /// Add the error that caused the rejection.
+    pub(crate) fn with_detail(mut self, detail: impl fmt::Display) -> Self {
+        self.detail = Some(detail.to_string());
+        self
+    }
+
+    /// Add the protobuf decoding error that caused the rejection, with the field where it failed.
+    pub(crate) fn with_decode_error(mut self, e: &DecodeError) -> Self {
+        let (field, description) = decode_error_location(e);
+        self.wire_type_mismatch = description
+            .strip_prefix("invalid wire type: ")
+            .and_then(|mismatch| mismatch.strip_suffix(')')?.split_once(" (expected "))
+            .map(|(actual, expected)| (actual.to_owned(), expected.to_owned()));
+        if let Some(field) = field {
+            self.field_violations
+                .push(FieldViolation { field, description });
+        }
+        self.with_detail(e)
+    }
+
+    /// Remove the paths of the fields that could not be decoded, which reveal details about the
+    /// message definitions.
+    pub(crate) fn without_diagnostics(mut self) -> Self {
+        // The decoding error starts with the path of the field: only its description is kept
+        if let Some(violation) = self.field_violations.first() {
+            self.detail = Some(violation.description.clone());
+        }
+        for violation in &mut self.field_violations {
+            violation.field.clear();
+        }
+        self.wire_type_mismatch = None;
+        self
+    }
+
+    /// Diagnostics about the error that caused the rejection, for debugging.
+    pub(crate) fn diagnostics(&self) -> Option<String> {
+        let mut lines: Vec<String> = self
+            .field_violations
+            .iter()
+            .map(|violation| format!("field: {}", violation.field))
+            .collect();
+        if let Some((actual, expected)) = &self.wire_type_mismatch {
+            lines.push(format!("wire type: {actual} (expected {expected})"));
+        }
+        // The body text of some rejections already contains the error
+        if let Some(detail) = self
+            .detail
+            .as_ref()
+            .filter(|d| !self.message.contains(d.as_str()))
+        {
+            lines.push(format!("error: {detail}"));
+        }
+        (!lines.is_empty()).then(|| lines.join("\n"))
+    }
+
+    /// The URI identifying the type of the rejection, which links to its documentation.
+    #[cfg(feature = "serde")]
+    pub(crate) fn type_uri(&self) -> String {
+        let (enum_name, variant_name) = self.variant;
+        format!(
+            "https://docs.rs/axum-protobuf/latest/axum_protobuf/enum.{enum_name}.html#variant.{variant_name}"
+        )
+    }
+}
+
+/// Split a decode error into the path of the field where decoding failed (if known) and a description.
+///
+/// prost doesn't expose the location of errors, so it is parsed from their `Display` implementation
+/// (`failed to decode Protobuf message: Inner.field: Outer.inner: description`, innermost first).
+pub(crate) fn decode_error_location(e: &DecodeError) -> (Option<String>, String) {
+    let text = e.to_string();
+    let mut rest = text
+        .strip_prefix("failed to decode Protobuf message: ")
+        .unwrap_or(&text);
+    let mut fields = Vec::new();
+    while let Some((location, tail)) = rest.split_once(": ") {
+        match location.split_once('.') {
+            Some((message, field)) if is_identifier(message) && is_identifier(field) => {
+                fields.push(field);
+                rest = tail;
+            }
+            _ => break,
+        }
+    }
+
+    let field = (!fields.is_empty()).then(|| {
+        fields.reverse();
+        fields.join(".")
+    });
+    (field, rest.to_owned())
+}
+
+fn is_identifier(s: &str) -> bool {
+    !s.is_empty()
+        && s.bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'#')
+}
+
+/// Replace the body of a rejection response, keeping its status and other headers.
+pub(crate) fn replace_body(res: Response, body: Vec<u8>, content_type: HeaderValue) -> Response {
+    let (mut parts, _) = res.into_parts();
+    parts.headers.insert(CONTENT_TYPE, content_type);
+    parts.headers.insert(CONTENT_LENGTH, body.len().into());
+    Response::from_parts(parts, Body::from(body))
+}
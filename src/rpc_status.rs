@@ -0,0 +1,132 @@
+use axum::http::{HeaderValue, StatusCode};
+use axum::response::Response;
+use prost::Message;
+
+use crate::named::with_message_type;
+#[cfg(feature = "serde")]
+use crate::protojson::JSON_CONTENT_TYPE;
+use crate::rejection::{RejectionDetails, replace_body};
+
+/// `google.rpc.Status`
+#[derive(Clone, PartialEq, Message)]
+struct Status {
+    #[prost(int32, tag = "1")]
+    code: i32,
+    #[prost(string, tag = "2")]
+    message: String,
+    #[prost(message, repeated, tag = "3")]
+    details: Vec<Any>,
+}
+
+/// `google.protobuf.Any`
+#[derive(Clone, PartialEq, Message)]
+struct Any {
+    #[prost(string, tag = "1")]
+    type_url: String,
+    #[prost(bytes = "vec", tag = "2")]
+    value: Vec<u8>,
+}
+
+/// `google.rpc.BadRequest`
+#[derive(Clone, PartialEq, Message)]
+struct BadRequest {
+    #[prost(message, repeated, tag = "1")]
+    field_violations: Vec<FieldViolation>,
+}
+
+/// `google.rpc.BadRequest.FieldViolation`
+#[derive(Clone, PartialEq, Message)]
+struct FieldViolation {
+    #[prost(string, tag = "1")]
+    field: String,
+    #[prost(string, tag = "2")]
+    description: String,
+}
+
+const BAD_REQUEST_TYPE_URL: &str = "type.googleapis.com/google.rpc.BadRequest";
+
+/// The `google.rpc.Code` corresponding to an HTTP status code.
+fn code(status: StatusCode) -> i32 {
+    match status {
+        // INVALID_ARGUMENT
+        StatusCode::BAD_REQUEST
+        | StatusCode::UNSUPPORTED_MEDIA_TYPE
+        | StatusCode::UNPROCESSABLE_ENTITY => 3,
+        // RESOURCE_EXHAUSTED, like gRPC for messages larger than the limit
+        StatusCode::PAYLOAD_TOO_LARGE => 8,
+        // INTERNAL
+        StatusCode::INTERNAL_SERVER_ERROR => 13,
+        // UNKNOWN
+        _ => 2,
+    }
+}
+
+/// Replace the body of a rejection response with a `google.rpc.Status` message, encoded as
+/// protobuf with the given content type.
+pub(crate) fn protobuf_response(
+    res: Response,
+    details: &RejectionDetails,
+    content_type: &HeaderValue,
+) -> Response {
+    let bad_request = BadRequest {
+        field_violations: details
+            .field_violations
+            .iter()
+            .map(|violation| FieldViolation {
+                field: violation.field.clone(),
+                description: violation.description.clone(),
+            })
+            .collect(),
+    };
+    let status = Status {
+        code: code(res.status()),
+        message: details.message.clone(),
+        details: (!bad_request.field_violations.is_empty())
+            .then(|| Any {
+                type_url: BAD_REQUEST_TYPE_URL.to_owned(),
+                value: bad_request.encode_to_vec(),
+            })
+            .into_iter()
+            .collect(),
+    };
+    let content_type = match content_type.to_str() {
+        Ok(content_type) => with_message_type(content_type, "google.rpc.Status"),
+        Err(_) => content_type.clone(),
+    };
+    replace_body(res, status.encode_to_vec(), content_type)
+}
+
+/// Replace the body of a rejection response with a `google.rpc.Status` message, encoded with
+/// its proto3 JSON representation.
+#[cfg(feature = "serde")]
+pub(crate) fn json_response(res: Response, details: &RejectionDetails) -> Response {
+    let mut status = serde_json::json!({
+        "code": code(res.status()),
+        "message": details.message,
+    });
+    if !details.field_violations.is_empty() {
+        let field_violations: Vec<_> = details
+            .field_violations
+            .iter()
+            .map(|violation| {
+                let mut field_violation = serde_json::json!({
+                    "description": violation.description,
+                });
+                // Empty strings are omitted by the proto3 JSON mapping
+                if !violation.field.is_empty() {
+                    field_violation["field"] = violation.field.as_str().into();
+                }
+                field_violation
+            })
+            .collect();
+        status["details"] = serde_json::json!([{
+            "@type": BAD_REQUEST_TYPE_URL,
+            "fieldViolations": field_violations,
+        }]);
+    }
+    replace_body(
+        res,
+        status.to_string().into_bytes(),
+        HeaderValue::from_static(JSON_CONTENT_TYPE),
+    )
+}
@@ -0,0 +1,41 @@
+use std::ops::Deref;
+
+use axum::response::{IntoResponse, Response};
+use prost::Message;
+
+use crate::encode_response;
+
+/// Protobuf Response for shared messages.
+///
+/// This behaves exactly like the [`Protobuf`](crate::Protobuf) response, but encodes a message
+/// behind a pointer, such as `Arc<T>` or `&'static T`, so that cached messages can be sent without
+/// being cloned.
+/// `Protobuf<Arc<T>>` and `Protobuf<&T>` can't be used for this, as prost doesn't implement
+/// [`prost::Message`] for these types.
+///
+/// ```rust
+/// use std::sync::Arc;
+///
+/// use axum::extract::State;
+/// use axum_protobuf::SharedProtobuf;
+/// # #[derive(Clone, PartialEq, prost::Message)]
+/// # struct Catalog {}
+///
+/// async fn handler(State(catalog): State<Arc<Catalog>>) -> SharedProtobuf<Arc<Catalog>> {
+///     SharedProtobuf(catalog)
+/// }
+/// ```
+///
+/// The message is still encoded for each response: to send the same message many times, prefer
+/// [`EncodedProtobuf`](crate::EncodedProtobuf), which encodes it once.
+pub struct SharedProtobuf<P>(pub P);
+
+impl<P> IntoResponse for SharedProtobuf<P>
+where
+    P: Deref,
+    P::Target: Message + Sized,
+{
+    fn into_response(self) -> Response {
+        encode_response(&*self.0)
+    }
+}
@@ -0,0 +1,276 @@
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+
+use axum::BoxError;
+use axum::body::{Body, BodyDataStream, Bytes};
+use axum::extract::FromRequest;
+use axum::http::StatusCode;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::request::Parts;
+use axum::response::{IntoResponse, Response};
+use bytes::{Buf, BytesMut};
+use futures_util::{Stream, StreamExt};
+use prost::Message;
+
+use crate::compression::ContentCoding;
+use crate::config::ProtobufResponse;
+use crate::rejection;
+use crate::{MediaType, PROTOBUF_CONTENT_TYPE, ProtobufConfig, ProtobufRejection};
+
+/// A varint is at most 10 bytes long.
+const MAX_VARINT_LEN: usize = 10;
+
+/// Protobuf Stream Extractor.
+///
+/// This decodes a request body made of length-delimited protobuf messages (each message is
+/// prefixed by its length, encoded as a varint), as produced by
+/// [`Message::encode_length_delimited`].
+///
+/// Messages are decoded one by one as the body is received, so the whole body is never buffered.
+/// This makes it suitable for bulk-ingest endpoints:
+///
+/// ```rust
+/// use axum_protobuf::ProtobufStream;
+/// use futures_util::StreamExt;
+/// # #[derive(Clone, PartialEq, prost::Message)]
+/// # struct Event {}
+///
+/// async fn handler(mut events: ProtobufStream<Event>) {
+///     while let Some(event) = events.next().await {
+///         match event {
+///             Ok(event) => { /* ... */ }
+///             Err(rejection) => { /* ... */ }
+///         }
+///     }
+/// }
+/// ```
+///
+/// The request will be rejected (and a [`ProtobufRejection`] will be returned) if the request
+/// doesn't have a `Content-Type: application/protobuf` (or similar) header, or if it has a
+/// `Content-Encoding` header (compressed bodies are not supported).
+///
+/// Then, the stream yields an error if:
+/// - A message failed to decode into the expected protobuf type (the next messages are still decoded).
+/// - A length prefix is invalid, or the body ends in the middle of a message.
+/// - A message is larger than [`ProtobufConfig::frame_limit`].
+/// - Buffering the request body fails.
+///
+/// The stream ends after yielding any of these errors, except decoding errors.
+///
+/// Note that the body limit set by axum's [`DefaultBodyLimit`](axum::extract::DefaultBodyLimit)
+/// or by [`ProtobufConfig::body_limit`] doesn't apply to the whole body, as it is not buffered.
+///
+/// ⚠️ Since it consumes the request body, the [`ProtobufStream`] extractor must be
+/// *last* if there are multiple extractors in a handler.
+/// See ["the order of extractors"](https://docs.rs/axum/latest/axum/extract/index.html#the-order-of-extractors).
+pub struct ProtobufStream<T> {
+    body: BodyDataStream,
+    buf: BytesMut,
+    frame_limit: usize,
+    body_ended: bool,
+    terminated: bool,
+    _message: PhantomData<fn() -> T>,
+}
+
+impl<S, T> FromRequest<S> for ProtobufStream<T>
+where
+    T: Message + Default,
+    S: Send + Sync,
+{
+    type Rejection = ProtobufRejection;
+
+    async fn from_request(req: axum::http::Request<Body>, _: &S) -> Result<Self, Self::Rejection> {
+        let (parts, body) = req.into_parts();
+        let frame_limit =
+            check_request(&parts).map_err(|rejection| rejection::handle(rejection, parts))?;
+
+        Ok(ProtobufStream {
+            body: body.into_data_stream(),
+            buf: BytesMut::new(),
+            frame_limit,
+            body_ended: false,
+            terminated: false,
+            _message: PhantomData,
+        })
+    }
+}
+
+/// Check the headers of a request before decoding its body as a stream, and get the frame limit.
+fn check_request(parts: &Parts) -> Result<usize, ProtobufRejection> {
+    let config = ProtobufConfig::from_extensions(&parts.extensions);
+    MediaType::from_content_type(&parts.headers)
+        .filter(|media_type| config.is_protobuf(media_type))
+        .ok_or(ProtobufRejection::MissingProtobufContentType)?;
+    if !ContentCoding::from_headers(&parts.headers)?.is_empty() {
+        return Err(ProtobufRejection::UnsupportedContentEncoding);
+    }
+    Ok(config.frame_limit)
+}
+
+impl<T> ProtobufStream<T>
+where
+    T: Message + Default,
+{
+    /// Attempt to decode the next message from the buffer.
+    ///
+    /// Returns `None` if more data is needed.
+    fn decode_frame(&mut self) -> Option<Result<T, ProtobufRejection>> {
+        let varint_len = match self
+            .buf
+            .iter()
+            .take(MAX_VARINT_LEN)
+            .position(|b| b & 0x80 == 0)
+        {
+            Some(position) => position + 1,
+            None if self.buf.len() >= MAX_VARINT_LEN => MAX_VARINT_LEN,
+            None => return None,
+        };
+
+        let frame_len = match prost::decode_length_delimiter(&self.buf[..varint_len]) {
+            Ok(frame_len) => frame_len,
+            Err(e) => {
+                self.terminated = true;
+                return Some(Err(ProtobufRejection::ProtobufDecodeError(e)));
+            }
+        };
+        if frame_len > self.frame_limit {
+            self.terminated = true;
+            return Some(Err(ProtobufRejection::PayloadTooLarge));
+        }
+
+        if self.buf.len() < varint_len + frame_len {
+            // Make room for the rest of the frame at once
+            self.buf.reserve(varint_len + frame_len - self.buf.len());
+            return None;
+        }
+
+        let mut frame = self.buf.split_to(varint_len + frame_len).freeze();
+        frame.advance(varint_len);
+        Some(T::decode(frame).map_err(ProtobufRejection::ProtobufDecodeError))
+    }
+}
+
+impl<T> Stream for ProtobufStream<T>
+where
+    T: Message + Default,
+{
+    type Item = Result<T, ProtobufRejection>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            if this.terminated {
+                return Poll::Ready(None);
+            }
+
+            if let Some(result) = this.decode_frame() {
+                return Poll::Ready(Some(result));
+            }
+
+            if this.body_ended {
+                this.terminated = true;
+                if this.buf.is_empty() {
+                    return Poll::Ready(None);
+                }
+                // The body ended in the middle of a frame: let prost report it
+                let error = T::decode_length_delimited(this.buf.split().freeze())
+                    .err()
+                    .map_or(ProtobufRejection::FailedToBufferBody, |e| {
+                        ProtobufRejection::ProtobufDecodeError(e)
+                    });
+                return Poll::Ready(Some(Err(error)));
+            }
+
+            match ready!(this.body.poll_next_unpin(cx)) {
+                Some(Ok(chunk)) => this.buf.extend_from_slice(&chunk),
+                Some(Err(_)) => {
+                    this.terminated = true;
+                    return Poll::Ready(Some(Err(ProtobufRejection::FailedToBufferBody)));
+                }
+                None => this.body_ended = true,
+            }
+        }
+    }
+}
+
+/// Protobuf Stream Response.
+///
+/// This sends a stream of messages as a response body made of length-delimited protobuf messages
+/// (each message is prefixed by its length, encoded as a varint), which can be read with
+/// [`ProtobufStream`] or [`Message::decode_length_delimited`].
+///
+/// Each message is encoded and sent as soon as it is produced by the stream, so the whole
+/// response is never buffered:
+///
+/// ```rust
+/// use axum_protobuf::ProtobufStreamResponse;
+/// use futures_util::stream;
+/// # #[derive(Clone, PartialEq, prost::Message)]
+/// # struct Row {}
+///
+/// async fn handler() -> ProtobufStreamResponse<Row> {
+///     ProtobufStreamResponse::new(stream::iter(vec![Row {}, Row {}]))
+/// }
+/// ```
+///
+/// If the stream can fail, use [`ProtobufStreamResponse::try_new`]: when an error occurs, the
+/// response body is aborted, so that the client can't mistake the partial response for a complete one.
+pub struct ProtobufStreamResponse<T> {
+    body: Body,
+    _message: PhantomData<fn() -> T>,
+}
+
+impl<T> ProtobufStreamResponse<T>
+where
+    T: Message + 'static,
+{
+    /// Create a response from a stream of messages.
+    pub fn new<S>(stream: S) -> Self
+    where
+        S: Stream<Item = T> + Send + 'static,
+    {
+        ProtobufStreamResponse {
+            body: Body::from_stream(stream.map(|message| encode_frame(&message))),
+            _message: PhantomData,
+        }
+    }
+
+    /// Create a response from a stream of messages that can fail.
+    ///
+    /// The response body ends with an error as soon as the stream yields one.
+    pub fn try_new<S, E>(stream: S) -> Self
+    where
+        S: Stream<Item = Result<T, E>> + Send + 'static,
+        E: Into<BoxError>,
+    {
+        ProtobufStreamResponse {
+            body: Body::from_stream(
+                stream.map(|message| encode_frame(&message.map_err(Into::into)?)),
+            ),
+            _message: PhantomData,
+        }
+    }
+}
+
+impl<T> IntoResponse for ProtobufStreamResponse<T> {
+    fn into_response(self) -> Response {
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, PROTOBUF_CONTENT_TYPE)
+            .extension(ProtobufResponse::default())
+            .body(self.body)
+            .unwrap() // we know this will be valid since we made it
+    }
+}
+
+fn encode_frame<T>(message: &T) -> Result<Bytes, BoxError>
+where
+    T: Message,
+{
+    let len = message.encoded_len();
+    let mut buf = BytesMut::with_capacity(prost::length_delimiter_len(len) + len);
+    message.encode_length_delimited(&mut buf)?;
+    Ok(buf.freeze())
+}
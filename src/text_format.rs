@@ -0,0 +1,197 @@
+use axum::body::Body;
+use axum::extract::{FromRequest, OptionalFromRequest};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use prost::Message;
+use prost_reflect::text_format::FormatOptions;
+use prost_reflect::{DynamicMessage, ReflectMessage};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::negotiate::Negotiation;
+use crate::protojson::{Decoder, Encoder, FromTextFn, json_from_bytes, json_response};
+use crate::{FallbackFormat, MediaType, Negotiate, ProtoJsonRejection, buffer_body};
+
+/// Content types of the protobuf text format.
+pub(crate) const TEXT_FORMAT_CONTENT_TYPES: [&str; 2] =
+    ["text/x-protobuf", "application/x-protobuf-text"];
+
+/// ProtoJson Extractor with support for the protobuf text format.
+///
+/// This works like [`ProtoJson`](crate::ProtoJson), but request bodies and responses can also use
+/// the [protobuf text format](https://protobuf.dev/reference/protobuf/textformat-spec/)
+/// (`text/x-protobuf` or `application/x-protobuf-text`), which is convenient for debugging with
+/// `curl`. Request bodies in this format follow the same limits as protobuf bodies.
+///
+/// Messages must implement `prost_reflect::ReflectMessage`, as their descriptor is needed to parse
+/// and print them in this format:
+///
+/// ```rust
+/// use axum::http::HeaderMap;
+/// use axum::response::Response;
+/// use axum_protobuf::ProtoJsonText;
+/// # #[derive(Clone, PartialEq, prost::Message, serde::Serialize, serde::Deserialize)]
+/// # struct User {}
+/// # impl prost_reflect::ReflectMessage for User {
+/// #     fn descriptor(&self) -> prost_reflect::MessageDescriptor { unimplemented!() }
+/// # }
+///
+/// async fn handler(headers: HeaderMap, ProtoJsonText(user): ProtoJsonText<User>) -> Response {
+///     ProtoJsonText(user).infer_response(&headers)
+/// }
+/// ```
+///
+/// Handlers can also return `Negotiate<ProtoJsonText<T>>`, so that the
+/// [`ProtobufConfig`](crate::ProtobufConfig) layer can choose the protobuf text format.
+///
+/// ⚠️ Since parsing the request body requires consuming it, the [`ProtoJsonText`] extractor must be
+/// *last* if there are multiple extractors in a handler.
+/// See ["the order of extractors"](https://docs.rs/axum/latest/axum/extract/index.html#the-order-of-extractors).
+pub struct ProtoJsonText<T>(pub T);
+
+impl<T> ProtoJsonText<T>
+where
+    T: Message + Serialize + ReflectMessage,
+{
+    /// Attempt to construct a response based on the `accept` header.
+    ///
+    /// See [`ProtoJson::try_infer_response`](crate::ProtoJson::try_infer_response) for more details.
+    pub fn try_infer_response(self, header_map: &HeaderMap) -> Option<Response> {
+        Self::encoder()
+            .infer_response(self.0, header_map, None)
+            .ok()
+    }
+
+    /// Construct a response based on the `accept` header.
+    ///
+    /// See [`ProtoJson::infer_response`](crate::ProtoJson::infer_response) for more details.
+    pub fn infer_response(self, header_map: &HeaderMap) -> Response {
+        self.infer_response_or(header_map, FallbackFormat::SameAsRequest)
+    }
+
+    /// Construct a response based on the `accept` header, or in the fallback format if it is not set.
+    ///
+    /// See [`ProtoJson::infer_response_or`](crate::ProtoJson::infer_response_or) for more details.
+    pub fn infer_response_or(self, header_map: &HeaderMap, fallback: FallbackFormat) -> Response {
+        Self::encoder()
+            .infer_response(self.0, header_map, Some(fallback))
+            .unwrap_or_else(IntoResponse::into_response)
+    }
+
+    /// How the messages are sent: JSON, protobuf or protobuf text format.
+    fn encoder() -> Encoder<T> {
+        Encoder {
+            json: json_response,
+            text_format: Some(response),
+        }
+    }
+}
+
+impl<T> ProtoJsonText<T>
+where
+    T: Message + Default + DeserializeOwned + ReflectMessage,
+{
+    /// How the request bodies are decoded: JSON, protobuf or protobuf text format.
+    fn decoder() -> Decoder<T> {
+        Decoder {
+            json: json_from_bytes,
+            text_format: Some(from_str),
+        }
+    }
+}
+
+impl<S, T> FromRequest<S> for ProtoJsonText<T>
+where
+    T: Message + Default + DeserializeOwned + ReflectMessage,
+    S: Send + Sync,
+{
+    type Rejection = ProtoJsonRejection;
+
+    async fn from_request(req: axum::http::Request<Body>, _: &S) -> Result<Self, Self::Rejection> {
+        Self::decoder().decode(req).await.map(Self)
+    }
+}
+/// A missing `content-type` header or an empty body gives `None`.
+/// Other errors are still rejected.
+impl<S, T> OptionalFromRequest<S> for ProtoJsonText<T>
+where
+    T: Message + Default + DeserializeOwned + ReflectMessage,
+    S: Send + Sync,
+{
+    type Rejection = ProtoJsonRejection;
+
+    async fn from_request(
+        req: axum::http::Request<Body>,
+        _: &S,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        Self::decoder()
+            .decode_optional(req)
+            .await
+            .map(|x| x.map(Self))
+    }
+}
+
+impl<T> IntoResponse for Negotiate<ProtoJsonText<T>>
+where
+    T: Message + Serialize + ReflectMessage + 'static,
+{
+    fn into_response(self) -> Response {
+        Negotiation::response(self.0.0, ProtoJsonText::encoder())
+    }
+}
+
+/// The content type of the protobuf text format designated by a media type, if any.
+pub(crate) fn content_type(media_type: &MediaType) -> Option<&'static str> {
+    TEXT_FORMAT_CONTENT_TYPES
+        .into_iter()
+        .find(|content_type| *content_type == media_type.essence())
+}
+
+/// Decode a request body in the protobuf text format.
+///
+/// The body is buffered like a protobuf body, so the same limits apply.
+/// Returns `None` if the body is empty.
+pub(crate) async fn decode_request<T>(
+    req: &mut axum::http::Request<Body>,
+    from_text: FromTextFn<T>,
+) -> Result<Option<T>, ProtoJsonRejection> {
+    let buf = buffer_body(req, None)
+        .await
+        .map_err(ProtoJsonRejection::ProtobufRejection)?;
+    if buf.is_empty() {
+        return Ok(None);
+    }
+
+    let text =
+        std::str::from_utf8(&buf).map_err(|e| ProtoJsonRejection::TextFormatError(e.into()))?;
+    from_text(text).map(Some)
+}
+
+/// Parse a message in the protobuf text format.
+fn from_str<T>(text: &str) -> Result<T, ProtoJsonRejection>
+where
+    T: ReflectMessage + Default,
+{
+    DynamicMessage::parse_text_format(T::default().descriptor(), text)
+        .map_err(|e| ProtoJsonRejection::TextFormatError(e.into()))?
+        .transcode_to()
+        .map_err(|e| ProtoJsonRejection::TextFormatError(e.into()))
+}
+
+/// Construct a response with a message in the (pretty-printed) protobuf text format.
+fn response<T>(message: &T, content_type: &'static str) -> Response
+where
+    T: ReflectMessage,
+{
+    let mut text = message
+        .transcode_to_dynamic()
+        .to_text_format_with_options(&FormatOptions::new().pretty(true));
+    text.push('\n');
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, content_type)
+        .body(Body::from(text))
+        .unwrap() // we know this will be valid since we made it
+}
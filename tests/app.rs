@@ -1,17 +1,366 @@
-use axum::Router;
-use axum::http::HeaderMap;
-use axum::response::Response;
+// Each test binary only uses some of these helpers
+#![allow(dead_code)]
+
+use std::sync::{Arc, LazyLock};
+
+use axum::body::Body;
+use axum::extract::DefaultBodyLimit;
+use axum::http::header::{ACCEPT, CONTENT_TYPE, LOCATION, VARY};
+use axum::http::request::Parts;
+use axum::http::{HeaderMap, HeaderValue, Request, StatusCode};
+use axum::response::{IntoResponse, Response};
 use axum::routing::{get, post};
-use axum_protobuf::{ProtoJson, Protobuf};
-use prost::Message;
+use axum::{Extension, Json, Router};
+#[cfg(feature = "proto3-json")]
+use axum_protobuf::Proto3Json;
+#[cfg(feature = "text-format")]
+use axum_protobuf::ProtoJsonText;
+use axum_protobuf::{
+    EncodedProtobuf, FallbackFormat, LimitedProtobuf, MediaType, NamedProtobuf, Negotiate,
+    ProtoJson, Protobuf, ProtobufConfig, ProtobufQuery, ProtobufRejection, ProtobufStream,
+    ProtobufStreamResponse, Rejection, SharedProtobuf, WireFormat,
+};
+use futures_util::{StreamExt, stream};
+use prost::{Message, Name};
 use serde::{Deserialize, Serialize};
+use tower::ServiceExt;
 
 pub fn build_app() -> Router {
     Router::new()
         .route("/protobuf/input", post(protobuf_input_handler))
+        .route(
+            "/protobuf/input/small",
+            post(protobuf_input_handler).layer(DefaultBodyLimit::max(8)),
+        )
+        .route(
+            "/protobuf/input/limited",
+            post(protobuf_limited_input_handler),
+        )
+        .route(
+            "/protobuf/input/content-type",
+            post(protobuf_content_type_input_handler),
+        )
+        .route(
+            "/protobuf/input/optional",
+            post(protobuf_optional_input_handler),
+        )
+        .route(
+            "/protobuf/input/config",
+            post(protobuf_input_handler).layer(
+                ProtobufConfig::new()
+                    .content_types(["application/octet-stream"])
+                    .body_limit(8)
+                    .reject_empty_body(true),
+            ),
+        )
+        .route(
+            "/protobuf/input/config-extension",
+            post(protobuf_input_handler)
+                .layer(Extension(ProtobufConfig::new().reject_empty_body(true))),
+        )
+        .route(
+            "/protobuf/input/compressed",
+            post(protobuf_input_handler).layer(ProtobufConfig::new().body_limit(64)),
+        )
+        .route(
+            "/protobuf/input/rpc-status",
+            post(protobuf_input_handler).layer(ProtobufConfig::new().rpc_status(true)),
+        )
+        .route(
+            "/protobuf/input/rpc-status/diagnostics",
+            post(protobuf_input_handler).layer(
+                ProtobufConfig::new()
+                    .rpc_status(true)
+                    .decode_diagnostics(true),
+            ),
+        )
+        .route(
+            "/protobuf/input/diagnostics",
+            post(protobuf_input_handler).layer(ProtobufConfig::new().decode_diagnostics(true)),
+        )
+        .route(
+            "/protobuf/input/problem",
+            post(protobuf_input_handler).layer(ProtobufConfig::new().problem_details(true)),
+        )
+        .route(
+            "/protobuf/input/problem/diagnostics",
+            post(protobuf_input_handler).layer(
+                ProtobufConfig::new()
+                    .problem_details(true)
+                    .decode_diagnostics(true),
+            ),
+        )
+        .route(
+            "/protobuf/input/custom-rejection",
+            post(protobuf_input_handler).layer(
+                ProtobufConfig::new()
+                    .rpc_status(true)
+                    .rejection_handler(custom_rejection),
+            ),
+        )
+        .route("/protobuf/named", post(protobuf_named_handler))
+        .route(
+            "/protobuf/named/config",
+            post(protobuf_named_handler)
+                .layer(ProtobufConfig::new().response_content_type("application/x-protobuf")),
+        )
         .route("/protobuf/output", get(protobuf_output_handler))
+        .route("/protobuf/output/boxed", get(protobuf_boxed_output_handler))
+        .route(
+            "/protobuf/output/shared",
+            get(protobuf_shared_output_handler),
+        )
+        .route(
+            "/protobuf/output/shared/static",
+            get(protobuf_static_output_handler),
+        )
+        .route(
+            "/protobuf/output/encoded",
+            get(protobuf_encoded_output_handler),
+        )
+        .route(
+            "/protobuf/output/config",
+            get(protobuf_output_handler)
+                .layer(ProtobufConfig::new().response_content_type("application/x-protobuf")),
+        )
+        .route(
+            "/protobuf/output/content-type",
+            get(protobuf_content_type_output_handler).layer(ProtobufConfig::new()),
+        )
+        .route(
+            "/protobuf/output/large",
+            get(protobuf_large_output_handler).layer(ProtobufConfig::new()),
+        )
+        .route(
+            "/protobuf/output/etag",
+            get(protobuf_output_handler).layer(ProtobufConfig::new().etag(true)),
+        )
+        .route(
+            "/protobuf/output/large/etag",
+            get(protobuf_large_output_handler).layer(ProtobufConfig::new().etag(true)),
+        )
+        .route("/protobuf/stream", post(protobuf_stream_handler))
+        .route(
+            "/protobuf/stream/config",
+            post(protobuf_stream_handler).layer(ProtobufConfig::new().frame_limit(8)),
+        )
+        .route(
+            "/protobuf/stream/output",
+            get(protobuf_stream_output_handler),
+        )
+        .route(
+            "/protobuf/stream/output/error",
+            get(protobuf_stream_output_error_handler),
+        )
+        .route("/protobuf/query", get(protobuf_query_handler))
+        .route(
+            "/protobuf/query/custom",
+            get(protobuf_query_handler).layer(ProtobufConfig::new().query_param("q")),
+        )
+        .route(
+            "/protobuf/query/custom-rejection",
+            get(protobuf_query_handler).layer(Extension(
+                ProtobufConfig::new().rejection_handler(custom_rejection),
+            )),
+        )
         .route("/protojson/input", post(protojson_input_handler))
+        .route(
+            "/protojson/input/small",
+            post(protojson_input_handler).layer(DefaultBodyLimit::max(8)),
+        )
+        .route(
+            "/protojson/input/unlimited",
+            post(protojson_input_handler).layer(DefaultBodyLimit::disable()),
+        )
+        .route(
+            "/protojson/input/optional",
+            post(protojson_optional_input_handler),
+        )
         .route("/protojson/output", get(protojson_output_handler))
+        .route("/protojson/echo", post(protojson_echo_handler))
+        .route("/protojson/format", post(protojson_format_handler))
+        .route(
+            "/protojson/input/problem",
+            post(protojson_input_handler)
+                .layer(ProtobufConfig::new().problem_details(true).rpc_status(true)),
+        )
+        .route(
+            "/protojson/input/custom-rejection",
+            post(protojson_input_handler)
+                .layer(ProtobufConfig::new().rejection_handler(custom_rejection)),
+        )
+        .route(
+            "/protojson/input/rpc-status",
+            post(protojson_input_handler).layer(
+                ProtobufConfig::new()
+                    .rpc_status(true)
+                    .decode_diagnostics(true),
+            ),
+        )
+        .route(
+            "/protojson/echo/fallback",
+            post(protojson_echo_fallback_handler),
+        )
+        .route(
+            "/protojson/negotiate",
+            get(protojson_negotiate_handler).layer(ProtobufConfig::new()),
+        )
+        .route(
+            "/protojson/negotiate/large",
+            get(protojson_negotiate_large_handler).layer(ProtobufConfig::new()),
+        )
+        .route(
+            "/protojson/negotiate/fallback",
+            get(protojson_negotiate_handler)
+                .layer(ProtobufConfig::new().fallback_format(FallbackFormat::Json)),
+        )
+        .route(
+            "/protojson/negotiate/fallback/protobuf",
+            get(protojson_negotiate_request_accept_handler)
+                .layer(ProtobufConfig::new().fallback_format(FallbackFormat::Protobuf)),
+        )
+        .route(
+            "/protojson/negotiate/created",
+            get(protojson_negotiate_created_handler).layer(ProtobufConfig::new()),
+        )
+        .route(
+            "/protojson/negotiate/error",
+            get(protojson_negotiate_error_handler).layer(ProtobufConfig::new()),
+        )
+        .route(
+            "/protojson/negotiate/no-layer",
+            get(protojson_negotiate_handler),
+        )
+        .route(
+            "/protojson/output/large",
+            get(protojson_large_output_handler),
+        )
+        .route(
+            "/protojson/output/large/config",
+            get(protojson_large_output_handler).layer(ProtobufConfig::new()),
+        )
+        .route(
+            "/protojson/output/large/uncompressed",
+            get(protojson_large_output_handler)
+                .layer(ProtobufConfig::new().compress_responses(false)),
+        )
+        .route(
+            "/protojson/output/encoded",
+            get(protojson_encoded_output_handler),
+        )
+        .merge(text_format_routes())
+        .merge(proto3_json_routes())
+}
+
+#[cfg(feature = "text-format")]
+fn text_format_routes() -> Router {
+    Router::new()
+        .route("/text-format/input", post(text_format_input_handler))
+        .route(
+            "/text-format/input/optional",
+            post(text_format_optional_input_handler),
+        )
+        .route("/text-format/output", get(text_format_output_handler))
+        .route(
+            "/text-format/negotiate",
+            get(text_format_negotiate_handler).layer(ProtobufConfig::new()),
+        )
+}
+
+#[cfg(not(feature = "text-format"))]
+fn text_format_routes() -> Router {
+    Router::new()
+}
+
+#[cfg(feature = "proto3-json")]
+fn proto3_json_routes() -> Router {
+    Router::new()
+        .route("/proto3-json/echo", post(proto3_json_echo_handler))
+        .route(
+            "/proto3-json/negotiate",
+            get(proto3_json_negotiate_handler).layer(ProtobufConfig::new()),
+        )
+        .route("/proto3-json/encoded", get(proto3_json_encoded_handler))
+}
+
+#[cfg(not(feature = "proto3-json"))]
+fn proto3_json_routes() -> Router {
+    Router::new()
+}
+
+/// Send a `GET` request to the app.
+pub async fn get_request(uri: &str, headers: &[(&str, &str)]) -> Response {
+    send_request("GET", uri, headers, Body::empty()).await
+}
+
+/// Send a `POST` request to the app.
+pub async fn post_request(uri: &str, headers: &[(&str, &str)], body: impl AsRef<[u8]>) -> Response {
+    let body = Body::from(body.as_ref().to_vec());
+    send_request("POST", uri, headers, body).await
+}
+
+async fn send_request(method: &str, uri: &str, headers: &[(&str, &str)], body: Body) -> Response {
+    let mut req = Request::builder().method(method).uri(uri);
+    for (name, value) in headers {
+        req = req.header(*name, *value);
+    }
+    build_app().oneshot(req.body(body).unwrap()).await.unwrap()
+}
+
+/// Messages of `google.rpc.Status` rejections.
+pub mod rpc {
+    use prost::Message;
+
+    /// `google.rpc.Status`
+    #[derive(Clone, PartialEq, Message)]
+    pub struct Status {
+        #[prost(int32, tag = "1")]
+        pub code: i32,
+        #[prost(string, tag = "2")]
+        pub message: String,
+        #[prost(message, repeated, tag = "3")]
+        pub details: Vec<Any>,
+    }
+
+    /// `google.protobuf.Any`
+    #[derive(Clone, PartialEq, Message)]
+    pub struct Any {
+        #[prost(string, tag = "1")]
+        pub type_url: String,
+        #[prost(bytes = "vec", tag = "2")]
+        pub value: Vec<u8>,
+    }
+
+    /// `google.rpc.BadRequest`
+    #[derive(Clone, PartialEq, Message)]
+    pub struct BadRequest {
+        #[prost(message, repeated, tag = "1")]
+        pub field_violations: Vec<FieldViolation>,
+    }
+
+    /// `google.rpc.BadRequest.FieldViolation`
+    #[derive(Clone, PartialEq, Message)]
+    pub struct FieldViolation {
+        #[prost(string, tag = "1")]
+        pub field: String,
+        #[prost(string, tag = "2")]
+        pub description: String,
+    }
+}
+
+pub fn custom_rejection(rejection: Rejection, request: &Parts) -> Response {
+    let kind = match rejection {
+        Rejection::Protobuf(_) => "protobuf",
+        Rejection::ProtoJson(_) => "protojson",
+        _ => "other",
+    };
+    let error = serde_json::json!({
+        "kind": kind,
+        "status": rejection.status().as_u16(),
+        "message": rejection.body_text(),
+        "path": request.uri.path(),
+    });
+    (rejection.status(), Json(error)).into_response()
 }
 
 #[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Message)]
@@ -20,11 +369,223 @@ pub struct TestMessage {
     pub test: String,
 }
 
+impl Name for TestMessage {
+    const NAME: &'static str = "TestMessage";
+    const PACKAGE: &'static str = "test";
+}
+
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Message)]
+pub struct Proto3Message {
+    #[prost(int64, tag = "1")]
+    pub big_number: i64,
+}
+
+/// Message with the well-known types that have a special proto3 JSON mapping.
+#[cfg(feature = "proto3-json")]
+#[derive(Clone, PartialEq, Message)]
+pub struct Proto3JsonMessage {
+    #[prost(int64, tag = "1")]
+    pub big_number: i64,
+    #[prost(enumeration = "Status", tag = "2")]
+    pub status: i32,
+    #[prost(message, optional, tag = "3")]
+    pub created_at: Option<prost_reflect::prost_types::Timestamp>,
+    #[prost(message, optional, tag = "4")]
+    pub nickname: Option<String>,
+    #[prost(message, optional, tag = "5")]
+    pub retries: Option<u32>,
+}
+
+#[cfg(feature = "proto3-json")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum Status {
+    Unspecified = 0,
+    Active = 1,
+    Archived = 2,
+}
+
+#[cfg(any(feature = "text-format", feature = "proto3-json"))]
+static DESCRIPTOR_POOL: LazyLock<prost_reflect::DescriptorPool> = LazyLock::new(|| {
+    use prost_reflect::prost_types::field_descriptor_proto::{Label, Type};
+    use prost_reflect::prost_types::{
+        DescriptorProto, EnumDescriptorProto, EnumValueDescriptorProto, FieldDescriptorProto,
+        FileDescriptorProto,
+    };
+
+    let field = |name: &str, number, r#type: Type, type_name: Option<&str>, json_name: &str| {
+        FieldDescriptorProto {
+            name: Some(name.to_owned()),
+            number: Some(number),
+            label: Some(Label::Optional as i32),
+            r#type: Some(r#type as i32),
+            type_name: type_name.map(str::to_owned),
+            json_name: Some(json_name.to_owned()),
+            ..Default::default()
+        }
+    };
+    let enum_value = |name: &str, number| EnumValueDescriptorProto {
+        name: Some(name.to_owned()),
+        number: Some(number),
+        ..Default::default()
+    };
+
+    // The global pool contains the well-known types
+    let mut pool = prost_reflect::DescriptorPool::global();
+    pool.add_file_descriptor_proto(FileDescriptorProto {
+        name: Some("test.proto".to_owned()),
+        package: Some("test".to_owned()),
+        dependency: vec![
+            "google/protobuf/timestamp.proto".to_owned(),
+            "google/protobuf/wrappers.proto".to_owned(),
+        ],
+        message_type: vec![
+            DescriptorProto {
+                name: Some("TestMessage".to_owned()),
+                field: vec![field("test", 1, Type::String, None, "test")],
+                ..Default::default()
+            },
+            DescriptorProto {
+                name: Some("Proto3JsonMessage".to_owned()),
+                field: vec![
+                    field("big_number", 1, Type::Int64, None, "bigNumber"),
+                    field("status", 2, Type::Enum, Some(".test.Status"), "status"),
+                    field(
+                        "created_at",
+                        3,
+                        Type::Message,
+                        Some(".google.protobuf.Timestamp"),
+                        "createdAt",
+                    ),
+                    field(
+                        "nickname",
+                        4,
+                        Type::Message,
+                        Some(".google.protobuf.StringValue"),
+                        "nickname",
+                    ),
+                    field(
+                        "retries",
+                        5,
+                        Type::Message,
+                        Some(".google.protobuf.UInt32Value"),
+                        "retries",
+                    ),
+                ],
+                ..Default::default()
+            },
+        ],
+        enum_type: vec![EnumDescriptorProto {
+            name: Some("Status".to_owned()),
+            value: vec![
+                enum_value("STATUS_UNSPECIFIED", 0),
+                enum_value("STATUS_ACTIVE", 1),
+                enum_value("STATUS_ARCHIVED", 2),
+            ],
+            ..Default::default()
+        }],
+        syntax: Some("proto3".to_owned()),
+        ..Default::default()
+    })
+    .unwrap();
+    pool
+});
+
+#[cfg(any(feature = "text-format", feature = "proto3-json"))]
+impl prost_reflect::ReflectMessage for TestMessage {
+    fn descriptor(&self) -> prost_reflect::MessageDescriptor {
+        DESCRIPTOR_POOL
+            .get_message_by_name("test.TestMessage")
+            .unwrap()
+    }
+}
+
+#[cfg(feature = "proto3-json")]
+impl prost_reflect::ReflectMessage for Proto3JsonMessage {
+    fn descriptor(&self) -> prost_reflect::MessageDescriptor {
+        DESCRIPTOR_POOL
+            .get_message_by_name("test.Proto3JsonMessage")
+            .unwrap()
+    }
+}
+
 #[axum::debug_handler]
 pub async fn protobuf_input_handler(Protobuf(input): Protobuf<TestMessage>) -> String {
     input.test
 }
 
+#[axum::debug_handler]
+pub async fn protobuf_limited_input_handler(
+    LimitedProtobuf(input): LimitedProtobuf<TestMessage, 8>,
+) -> String {
+    input.test
+}
+
+#[axum::debug_handler]
+pub async fn protobuf_content_type_input_handler(
+    content_type: Option<MediaType>,
+    Protobuf(_): Protobuf<TestMessage>,
+) -> String {
+    content_type
+        .and_then(|x| x.param("messageType").map(str::to_owned))
+        .unwrap_or_default()
+}
+
+#[axum::debug_handler]
+pub async fn protobuf_optional_input_handler(input: Option<Protobuf<TestMessage>>) -> String {
+    match input {
+        Some(Protobuf(input)) => input.test,
+        None => "none".to_owned(),
+    }
+}
+
+#[axum::debug_handler]
+pub async fn protobuf_named_handler(
+    NamedProtobuf(input): NamedProtobuf<TestMessage>,
+) -> NamedProtobuf<TestMessage> {
+    NamedProtobuf(input)
+}
+
+#[axum::debug_handler]
+pub async fn protobuf_stream_handler(
+    mut input: ProtobufStream<TestMessage>,
+) -> Result<String, ProtobufRejection> {
+    let mut tests = Vec::new();
+    while let Some(message) = input.next().await {
+        tests.push(message?.test);
+    }
+    Ok(tests.join(","))
+}
+
+#[axum::debug_handler]
+pub async fn protobuf_large_output_handler() -> Protobuf<TestMessage> {
+    Protobuf(TestMessage {
+        test: "test".repeat(1000),
+    })
+}
+
+#[axum::debug_handler]
+pub async fn protobuf_stream_output_handler() -> ProtobufStreamResponse<TestMessage> {
+    ProtobufStreamResponse::new(stream::iter(["a", "b", "c"].map(|test| TestMessage {
+        test: test.to_owned(),
+    })))
+}
+
+#[axum::debug_handler]
+pub async fn protobuf_stream_output_error_handler() -> ProtobufStreamResponse<TestMessage> {
+    ProtobufStreamResponse::try_new(stream::iter([
+        Ok(TestMessage {
+            test: "a".to_owned(),
+        }),
+        Err("something went wrong"),
+    ]))
+}
+
+#[axum::debug_handler]
+pub async fn protobuf_query_handler(ProtobufQuery(input): ProtobufQuery<TestMessage>) -> String {
+    input.test
+}
+
 #[axum::debug_handler]
 pub async fn protobuf_output_handler() -> Protobuf<TestMessage> {
     Protobuf(TestMessage {
@@ -32,11 +593,63 @@ pub async fn protobuf_output_handler() -> Protobuf<TestMessage> {
     })
 }
 
+pub async fn protobuf_content_type_output_handler() -> impl IntoResponse {
+    (
+        [(CONTENT_TYPE, "application/vnd.acme+proto")],
+        Protobuf(TestMessage {
+            test: "test".to_owned(),
+        }),
+    )
+}
+
+#[axum::debug_handler]
+pub async fn protobuf_boxed_output_handler() -> Protobuf<Box<TestMessage>> {
+    Protobuf(Box::new(TestMessage {
+        test: "test".to_owned(),
+    }))
+}
+
+static SHARED_MESSAGE: LazyLock<Arc<TestMessage>> = LazyLock::new(|| {
+    Arc::new(TestMessage {
+        test: "test".to_owned(),
+    })
+});
+
+#[axum::debug_handler]
+pub async fn protobuf_shared_output_handler() -> SharedProtobuf<Arc<TestMessage>> {
+    SharedProtobuf(Arc::clone(&SHARED_MESSAGE))
+}
+
+#[axum::debug_handler]
+pub async fn protobuf_static_output_handler() -> SharedProtobuf<&'static TestMessage> {
+    SharedProtobuf(&SHARED_MESSAGE)
+}
+
+static ENCODED_MESSAGE: LazyLock<EncodedProtobuf<TestMessage>> = LazyLock::new(|| {
+    EncodedProtobuf::with_json(&TestMessage {
+        test: "test".to_owned(),
+    })
+    .unwrap()
+});
+
+#[axum::debug_handler]
+pub async fn protobuf_encoded_output_handler() -> EncodedProtobuf<TestMessage> {
+    ENCODED_MESSAGE.clone()
+}
+
 #[axum::debug_handler]
 pub async fn protojson_input_handler(ProtoJson(input): ProtoJson<TestMessage>) -> String {
     input.test
 }
 
+#[axum::debug_handler]
+pub async fn protojson_optional_input_handler(input: Option<ProtoJson<TestMessage>>) -> String {
+    match input {
+        Some(ProtoJson(input)) => input.test,
+        None => "none".to_owned(),
+    }
+}
+
 #[axum::debug_handler]
 pub async fn protojson_output_handler(headers: HeaderMap) -> Response {
     ProtoJson(TestMessage {
@@ -44,3 +657,161 @@ pub async fn protojson_output_handler(headers: HeaderMap) -> Response {
     })
     .infer_response(&headers)
 }
+
+#[axum::debug_handler]
+pub async fn protojson_large_output_handler(headers: HeaderMap) -> Response {
+    ProtoJson(TestMessage {
+        test: "test".repeat(1000),
+    })
+    .infer_response(&headers)
+}
+
+#[axum::debug_handler]
+pub async fn protojson_encoded_output_handler(headers: HeaderMap) -> Response {
+    ENCODED_MESSAGE.clone().infer_response(&headers)
+}
+
+#[axum::debug_handler]
+pub async fn protojson_echo_handler(
+    headers: HeaderMap,
+    ProtoJson(input): ProtoJson<Proto3Message>,
+) -> Response {
+    ProtoJson(input).infer_response(&headers)
+}
+
+#[axum::debug_handler]
+pub async fn protojson_format_handler(
+    format: Option<WireFormat>,
+    ProtoJson(input): ProtoJson<TestMessage>,
+) -> String {
+    format!("{format:?} {}", input.test)
+}
+
+#[axum::debug_handler]
+pub async fn protojson_echo_fallback_handler(
+    headers: HeaderMap,
+    ProtoJson(input): ProtoJson<Proto3Message>,
+) -> Response {
+    ProtoJson(input).infer_response_or(&headers, FallbackFormat::SameAsRequest)
+}
+
+#[axum::debug_handler]
+pub async fn protojson_negotiate_handler() -> Negotiate<TestMessage> {
+    Negotiate(TestMessage {
+        test: "test".to_owned(),
+    })
+}
+
+#[cfg(feature = "text-format")]
+#[axum::debug_handler]
+pub async fn text_format_input_handler(ProtoJsonText(input): ProtoJsonText<TestMessage>) -> String {
+    input.test
+}
+
+#[cfg(feature = "text-format")]
+#[axum::debug_handler]
+pub async fn text_format_optional_input_handler(
+    input: Option<ProtoJsonText<TestMessage>>,
+) -> String {
+    match input {
+        Some(ProtoJsonText(input)) => input.test,
+        None => "none".to_owned(),
+    }
+}
+
+#[cfg(feature = "text-format")]
+#[axum::debug_handler]
+pub async fn text_format_output_handler(headers: HeaderMap) -> Response {
+    ProtoJsonText(TestMessage {
+        test: "test".to_owned(),
+    })
+    .infer_response(&headers)
+}
+
+#[cfg(feature = "text-format")]
+#[axum::debug_handler]
+pub async fn text_format_negotiate_handler() -> Negotiate<ProtoJsonText<TestMessage>> {
+    Negotiate(ProtoJsonText(TestMessage {
+        test: "test".to_owned(),
+    }))
+}
+
+#[cfg(feature = "proto3-json")]
+#[axum::debug_handler]
+pub async fn proto3_json_echo_handler(
+    headers: HeaderMap,
+    Proto3Json(input): Proto3Json<Proto3JsonMessage>,
+) -> Response {
+    Proto3Json(input).infer_response(&headers)
+}
+
+#[cfg(feature = "proto3-json")]
+fn proto3_json_message() -> Proto3JsonMessage {
+    Proto3JsonMessage {
+        big_number: 9007199254740993,
+        status: Status::Active as i32,
+        created_at: Some(prost_reflect::prost_types::Timestamp {
+            seconds: 1704164645,
+            nanos: 0,
+        }),
+        nickname: Some(String::new()),
+        retries: None,
+    }
+}
+
+#[cfg(feature = "proto3-json")]
+#[axum::debug_handler]
+pub async fn proto3_json_negotiate_handler() -> Negotiate<Proto3Json<Proto3JsonMessage>> {
+    Negotiate(Proto3Json(proto3_json_message()))
+}
+
+#[cfg(feature = "proto3-json")]
+#[axum::debug_handler]
+pub async fn proto3_json_encoded_handler(headers: HeaderMap) -> Response {
+    EncodedProtobuf::with_proto3_json(&proto3_json_message())
+        .unwrap()
+        .infer_response(&headers)
+}
+
+/// Send the `accept` header seen by the handler in the `x-request-accept` header.
+#[axum::debug_handler]
+pub async fn protojson_negotiate_request_accept_handler(headers: HeaderMap) -> impl IntoResponse {
+    let accept = headers
+        .get(ACCEPT)
+        .cloned()
+        .unwrap_or(HeaderValue::from_static("none"));
+    (
+        [("x-request-accept", accept)],
+        Negotiate(TestMessage {
+            test: "test".to_owned(),
+        }),
+    )
+}
+
+#[axum::debug_handler]
+pub async fn protojson_negotiate_created_handler() -> impl IntoResponse {
+    (
+        StatusCode::CREATED,
+        [(LOCATION, "/test"), (VARY, "origin")],
+        Negotiate(TestMessage {
+            test: "test".to_owned(),
+        }),
+    )
+}
+
+#[axum::debug_handler]
+pub async fn protojson_negotiate_error_handler() -> impl IntoResponse {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Negotiate(TestMessage {
+            test: "error".to_owned(),
+        }),
+    )
+}
+
+#[axum::debug_handler]
+pub async fn protojson_negotiate_large_handler() -> Negotiate<TestMessage> {
+    Negotiate(TestMessage {
+        test: "test".repeat(1000),
+    })
+}
@@ -0,0 +1,300 @@
+mod app;
+
+use app::{get_request, post_request};
+use axum::body::to_bytes;
+use axum::http::StatusCode;
+#[cfg(feature = "gzip")]
+use axum::response::Response;
+use prost::Message;
+
+use crate::app::TestMessage;
+
+fn encoded_message(test: &str) -> Vec<u8> {
+    let mut input = Vec::new();
+    TestMessage {
+        test: test.to_owned(),
+    }
+    .encode(&mut input)
+    .unwrap();
+    input
+}
+
+async fn send(uri: &str, encoding: &str, input: Vec<u8>) -> (StatusCode, String) {
+    let res = post_request(
+        uri,
+        &[
+            ("Content-Type", "application/protobuf"),
+            ("Content-Encoding", encoding),
+        ],
+        input,
+    )
+    .await;
+    let status = res.status();
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    dbg!(&body);
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[cfg(feature = "gzip")]
+fn gzip(input: &[u8]) -> Vec<u8> {
+    use std::io::Write;
+
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(input).unwrap();
+    encoder.finish().unwrap()
+}
+
+#[tokio::test]
+async fn compression_identity() {
+    let res = send("/protobuf/input", "identity", encoded_message("test")).await;
+    assert_eq!(res, (StatusCode::OK, "test".to_owned()));
+}
+
+#[tokio::test]
+async fn compression_unsupported() {
+    let res = send("/protobuf/input", "compress", encoded_message("test")).await;
+    assert_eq!(
+        res,
+        (
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "Unsupported 'content-encoding' header".to_owned()
+        )
+    );
+}
+
+#[cfg(feature = "gzip")]
+#[tokio::test]
+async fn compression_gzip() {
+    let input = gzip(&encoded_message("test"));
+    let res = send("/protobuf/input", "gzip", input).await;
+    assert_eq!(res, (StatusCode::OK, "test".to_owned()));
+}
+
+#[cfg(feature = "gzip")]
+#[tokio::test]
+async fn compression_gzip_invalid() {
+    let res = send("/protobuf/input", "gzip", encoded_message("test")).await;
+    assert_eq!(
+        res,
+        (
+            StatusCode::BAD_REQUEST,
+            "Error decompressing request body".to_owned()
+        )
+    );
+}
+
+#[cfg(feature = "gzip")]
+#[tokio::test]
+async fn compression_gzip_body_limit() {
+    // The compressed body is smaller than the limit, but not the decompressed one
+    let input = gzip(&encoded_message(&"a".repeat(1000)));
+    assert!(input.len() < 64);
+    let routes = ["/protobuf/input/small", "/protobuf/input/compressed"];
+
+    for uri in routes {
+        println!("Trying URI: {uri}");
+        let res = send(uri, "gzip", input.clone()).await;
+        assert_eq!(
+            res,
+            (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "Request body is too large".to_owned()
+            )
+        );
+    }
+}
+
+#[cfg(feature = "deflate")]
+#[tokio::test]
+async fn compression_deflate() {
+    use std::io::Write;
+
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(&encoded_message("test")).unwrap();
+    let input = encoder.finish().unwrap();
+    let res = send("/protobuf/input", "deflate", input).await;
+    assert_eq!(res, (StatusCode::OK, "test".to_owned()));
+}
+
+#[cfg(feature = "brotli")]
+#[tokio::test]
+async fn compression_brotli() {
+    let mut input = Vec::new();
+    brotli::BrotliCompress(
+        &mut encoded_message("test").as_slice(),
+        &mut input,
+        &Default::default(),
+    )
+    .unwrap();
+    let res = send("/protobuf/input", "br", input).await;
+    assert_eq!(res, (StatusCode::OK, "test".to_owned()));
+}
+
+#[cfg(feature = "zstd")]
+#[tokio::test]
+async fn compression_zstd() {
+    let input = zstd::encode_all(encoded_message("test").as_slice(), 0).unwrap();
+    let res = send("/protobuf/input", "zstd", input).await;
+    assert_eq!(res, (StatusCode::OK, "test".to_owned()));
+}
+
+#[cfg(all(feature = "gzip", feature = "zstd"))]
+#[tokio::test]
+async fn compression_multiple() {
+    let input = zstd::encode_all(gzip(&encoded_message("test")).as_slice(), 0).unwrap();
+    let res = send("/protobuf/input", "gzip, zstd", input).await;
+    assert_eq!(res, (StatusCode::OK, "test".to_owned()));
+}
+
+#[tokio::test]
+async fn compression_response_no_accept_encoding() {
+    let res = get_request("/protobuf/output/large", &[]).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers().get("Content-Encoding"), None);
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    let message = TestMessage::decode(body).unwrap();
+    assert_eq!(message.test, "test".repeat(1000));
+}
+
+#[cfg(feature = "gzip")]
+async fn gunzip_body(res: Response) -> Vec<u8> {
+    use std::io::Read;
+
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    let mut output = Vec::new();
+    flate2::read::GzDecoder::new(body.as_ref())
+        .read_to_end(&mut output)
+        .unwrap();
+    output
+}
+
+#[cfg(feature = "gzip")]
+#[tokio::test]
+async fn compression_response_gzip() {
+    let res = get_request("/protobuf/output/large", &[("Accept-Encoding", "gzip")]).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers().get("Content-Encoding").unwrap(), "gzip");
+    assert_eq!(res.headers().get("Vary").unwrap(), "accept-encoding");
+    assert_eq!(res.headers().get("Content-Length"), None);
+    let body = gunzip_body(res).await;
+    let message = TestMessage::decode(body.as_slice()).unwrap();
+    assert_eq!(message.test, "test".repeat(1000));
+}
+
+#[cfg(feature = "gzip")]
+#[tokio::test]
+async fn compression_response_below_threshold() {
+    let res = get_request("/protobuf/output/config", &[("Accept-Encoding", "gzip")]).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers().get("Content-Encoding"), None);
+    assert_eq!(res.headers().get("Vary").unwrap(), "accept-encoding");
+}
+
+#[cfg(feature = "gzip")]
+#[tokio::test]
+async fn compression_response_not_acceptable() {
+    let accept_encodings = ["identity", "gzip;q=0", "*;q=0", "compress"];
+
+    for accept_encoding in accept_encodings {
+        println!("Trying Accept-Encoding: {accept_encoding}");
+        let res = get_request(
+            "/protobuf/output/large",
+            &[("Accept-Encoding", accept_encoding)],
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get("Content-Encoding"), None);
+    }
+}
+
+#[cfg(feature = "gzip")]
+#[tokio::test]
+async fn compression_response_protojson() {
+    let res = get_request(
+        "/protojson/output/large",
+        &[("Accept", "application/json"), ("Accept-Encoding", "gzip")],
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers().get("Content-Encoding").unwrap(), "gzip");
+    let body = gunzip_body(res).await;
+    let message = serde_json::from_slice::<TestMessage>(&body).unwrap();
+    assert_eq!(message.test, "test".repeat(1000));
+}
+
+#[cfg(feature = "gzip")]
+#[tokio::test]
+async fn compression_response_protojson_config() {
+    let headers = [("Accept", "application/json"), ("Accept-Encoding", "gzip")];
+
+    let res = get_request("/protojson/output/large/config", &headers).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers().get("Content-Encoding").unwrap(), "gzip");
+    let vary: Vec<_> = res.headers().get_all("Vary").iter().collect();
+    assert_eq!(vary, ["accept", "accept-encoding"]);
+    let body = gunzip_body(res).await;
+    let message = serde_json::from_slice::<TestMessage>(&body).unwrap();
+    assert_eq!(message.test, "test".repeat(1000));
+
+    let res = get_request("/protojson/output/large/uncompressed", &headers).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers().get("Content-Encoding"), None);
+    let length = res.headers().get("Content-Length").unwrap().clone();
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(length, body.len().to_string());
+    let message = serde_json::from_slice::<TestMessage>(&body).unwrap();
+    assert_eq!(message.test, "test".repeat(1000));
+}
+
+#[cfg(all(
+    feature = "gzip",
+    feature = "deflate",
+    feature = "brotli",
+    feature = "zstd"
+))]
+#[tokio::test]
+async fn compression_response_negotiation() {
+    let accept_encodings = [
+        ("gzip, deflate, br, zstd", "zstd"),
+        ("gzip, deflate, br", "br"),
+        ("gzip;q=0.5, deflate;q=0.8", "deflate"),
+        ("zstd;q=0.5, gzip", "gzip"),
+        ("*", "zstd"),
+        ("*, zstd;q=0", "br"),
+        ("Gzip;Q=0.9, br;q=0.1", "gzip"),
+        // Invalid q-values are ignored
+        ("zstd;q=5, gzip;q=0.5", "gzip"),
+        ("zstd;q=inf, br;q=1e9, deflate;q=0.001", "deflate"),
+        ("zstd;q=0.0001, gzip;q=1.000", "gzip"),
+    ];
+
+    for (accept_encoding, expected) in accept_encodings {
+        println!("Trying Accept-Encoding: {accept_encoding}");
+        let res = get_request(
+            "/protobuf/output/large",
+            &[("Accept-Encoding", accept_encoding)],
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get("Content-Encoding").unwrap(), expected);
+    }
+}
+
+#[cfg(feature = "gzip")]
+#[tokio::test]
+async fn compression_response_negotiated() {
+    let res = get_request(
+        "/protojson/negotiate/large",
+        &[("Accept", "application/json"), ("Accept-Encoding", "gzip")],
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        res.headers().get("Content-Type").unwrap(),
+        "application/json"
+    );
+    assert_eq!(res.headers().get("Content-Encoding").unwrap(), "gzip");
+    let body = gunzip_body(res).await;
+    let message: TestMessage = serde_json::from_slice(&body).unwrap();
+    assert_eq!(message.test, "test".repeat(1000));
+}
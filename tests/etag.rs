@@ -0,0 +1,114 @@
+mod app;
+
+use app::get_request;
+use axum::body::to_bytes;
+use axum::http::StatusCode;
+use prost::Message;
+
+use crate::app::TestMessage;
+
+async fn get_etag(uri: &str) -> String {
+    let res = get_request(uri, &[]).await;
+    res.headers()
+        .get("ETag")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_owned()
+}
+
+#[tokio::test]
+async fn etag_disabled() {
+    let res = get_request("/protobuf/output", &[]).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers().get("ETag"), None);
+}
+
+#[tokio::test]
+async fn etag_response() {
+    let res = get_request("/protobuf/output/etag", &[]).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let etag = res
+        .headers()
+        .get("ETag")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_owned();
+    assert!(etag.starts_with('"') && etag.ends_with('"'));
+    assert_eq!(res.headers().get("Content-Length").unwrap(), "6");
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    let message = TestMessage::decode(body).unwrap();
+    assert_eq!(message.test, "test");
+
+    // The entity tag only depends on the body
+    assert_eq!(get_etag("/protobuf/output/etag").await, etag);
+    assert_ne!(get_etag("/protobuf/output/large/etag").await, etag);
+}
+
+#[tokio::test]
+async fn etag_not_modified() {
+    let etag = get_etag("/protobuf/output/etag").await;
+
+    for if_none_match in [
+        etag.clone(),
+        format!("W/{etag}"),
+        format!("\"other\", {etag}"),
+        "*".to_owned(),
+    ] {
+        println!("Trying {if_none_match}");
+        let res = get_request(
+            "/protobuf/output/etag",
+            &[("If-None-Match", &if_none_match)],
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(res.headers().get("ETag").unwrap(), etag.as_str());
+        assert_eq!(res.headers().get("Content-Type"), None);
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        assert!(body.is_empty());
+    }
+}
+
+#[tokio::test]
+async fn etag_modified() {
+    let res = get_request("/protobuf/output/etag", &[("If-None-Match", "\"other\"")]).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    let message = TestMessage::decode(body).unwrap();
+    assert_eq!(message.test, "test");
+}
+
+#[cfg(feature = "gzip")]
+#[tokio::test]
+async fn etag_compressed() {
+    let etag = get_etag("/protobuf/output/large/etag").await;
+
+    let res = get_request(
+        "/protobuf/output/large/etag",
+        &[("Accept-Encoding", "gzip")],
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers().get("Content-Encoding").unwrap(), "gzip");
+    let gzip_etag = res.headers().get("ETag").unwrap().to_str().unwrap();
+    assert_ne!(gzip_etag, etag);
+    assert!(gzip_etag.ends_with("-gzip\""));
+
+    let res = get_request(
+        "/protobuf/output/large/etag",
+        &[("Accept-Encoding", "gzip"), ("If-None-Match", gzip_etag)],
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(res.headers().get("Content-Encoding"), None);
+    assert_eq!(res.headers().get("Vary").unwrap(), "accept-encoding");
+
+    // The uncompressed representation has a different entity tag
+    let res = get_request(
+        "/protobuf/output/large/etag",
+        &[("If-None-Match", gzip_etag)],
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::OK);
+}
@@ -0,0 +1,64 @@
+use axum_protobuf::MediaType;
+
+#[test]
+fn media_type_simple() {
+    let media_type = MediaType::parse("application/protobuf").unwrap();
+    assert_eq!(media_type.essence(), "application/protobuf");
+    assert_eq!(media_type.type_(), "application");
+    assert_eq!(media_type.subtype(), "protobuf");
+    assert_eq!(media_type.suffix(), None);
+    assert_eq!(media_type.params().count(), 0);
+}
+
+#[test]
+fn media_type_case_insensitive() {
+    let media_type = MediaType::parse("Application/JSON; CharSet=UTF-8").unwrap();
+    assert_eq!(media_type.essence(), "application/json");
+    assert_eq!(media_type.param("charset"), Some("UTF-8"));
+    assert_eq!(media_type.param("CHARSET"), Some("UTF-8"));
+}
+
+#[test]
+fn media_type_params() {
+    let media_type =
+        MediaType::parse("application/x-protobuf ; messageType=\"foo.Bar\";charset=utf-8;")
+            .unwrap();
+    assert_eq!(media_type.essence(), "application/x-protobuf");
+    assert_eq!(
+        media_type.params().collect::<Vec<_>>(),
+        vec![("messagetype", "foo.Bar"), ("charset", "utf-8")]
+    );
+}
+
+#[test]
+fn media_type_quoted_string() {
+    let media_type = MediaType::parse(r#"text/plain; a="b;c\"d""#).unwrap();
+    assert_eq!(media_type.param("a"), Some("b;c\"d"));
+}
+
+#[test]
+fn media_type_suffix() {
+    let media_type = MediaType::parse("application/vnd.api+json").unwrap();
+    assert_eq!(media_type.subtype(), "vnd.api+json");
+    assert_eq!(media_type.suffix(), Some("json"));
+}
+
+#[test]
+fn media_type_invalid() {
+    let inputs = [
+        "",
+        "application",
+        "application/",
+        "/json",
+        "application json/x",
+        "application/json; charset",
+        "application/json; charset=\"utf-8",
+        "application/json; charset=utf 8",
+        "application/json charset=utf-8",
+    ];
+
+    for input in inputs {
+        println!("Trying media type: {input}");
+        assert_eq!(MediaType::parse(input), None);
+    }
+}
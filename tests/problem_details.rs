@@ -0,0 +1,131 @@
+mod app;
+
+use app::post_request;
+use axum::body::to_bytes;
+use axum::http::StatusCode;
+use axum::response::Response;
+use serde_json::{Value, json};
+
+const DOCS: &str = "https://docs.rs/axum-protobuf/latest/axum_protobuf";
+
+async fn read_problem(res: Response) -> Value {
+    assert_eq!(
+        res.headers().get("Content-Type").unwrap(),
+        "application/problem+json"
+    );
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    dbg!(&body);
+    serde_json::from_slice(&body).unwrap()
+}
+
+#[tokio::test]
+async fn problem_details_decode_error() {
+    let res = post_request(
+        "/protobuf/input/problem",
+        &[("Content-Type", "application/protobuf")],
+        b"\x0a\x01\xff",
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    // The field is only sent with decode diagnostics
+    assert_eq!(
+        read_problem(res).await,
+        json!({
+            "type": format!("{DOCS}/enum.ProtobufRejection.html#variant.ProtobufDecodeError"),
+            "title": "Protobuf decoding error",
+            "status": 400,
+            "detail": "invalid string value: data is not UTF-8 encoded",
+            "errors": [{
+                "detail": "invalid string value: data is not UTF-8 encoded",
+            }],
+        })
+    );
+}
+
+#[tokio::test]
+async fn problem_details_decode_diagnostics() {
+    let res = post_request(
+        "/protobuf/input/problem/diagnostics",
+        &[("Content-Type", "application/protobuf")],
+        b"\x0a\x01\xff",
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        read_problem(res).await,
+        json!({
+            "type": format!("{DOCS}/enum.ProtobufRejection.html#variant.ProtobufDecodeError"),
+            "title": "Protobuf decoding error",
+            "status": 400,
+            "detail": "failed to decode Protobuf message: TestMessage.test: invalid string value: data is not UTF-8 encoded",
+            "errors": [{
+                "field": "test",
+                "detail": "invalid string value: data is not UTF-8 encoded",
+            }],
+        })
+    );
+}
+
+#[tokio::test]
+async fn problem_details_missing_content_type() {
+    let res = post_request("/protobuf/input/problem", &[], b"").await;
+    assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    assert_eq!(
+        read_problem(res).await,
+        json!({
+            "type": format!("{DOCS}/enum.ProtobufRejection.html#variant.MissingProtobufContentType"),
+            "title": "Missing 'content-type: application/protobuf' header",
+            "status": 415,
+        })
+    );
+}
+
+#[tokio::test]
+async fn problem_details_protojson() {
+    let res = post_request(
+        "/protojson/input/problem",
+        &[("Content-Type", "application/json")],
+        b"{",
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let problem = read_problem(res).await;
+    assert_eq!(
+        problem["type"],
+        format!("{DOCS}/enum.ProtoJsonRejection.html#variant.JsonRejection")
+    );
+    assert_eq!(problem["title"], "Failed to decode the JSON request body");
+    assert_eq!(problem["status"], 400);
+    assert!(problem["detail"].is_string());
+
+    let res = post_request(
+        "/protojson/input/problem",
+        &[("Accept", "application/json")],
+        b"",
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    assert_eq!(
+        read_problem(res).await,
+        json!({
+            "type": format!("{DOCS}/enum.ProtoJsonRejection.html#variant.MissingContentType"),
+            "title": "Missing 'content-type' header",
+            "status": 415,
+        })
+    );
+}
+
+#[tokio::test]
+async fn problem_details_rpc_status() {
+    let res = post_request(
+        "/protojson/input/problem",
+        &[("Content-Type", "application/protobuf")],
+        b"\x0a\x01\xff",
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        res.headers().get("Content-Type").unwrap(),
+        "application/protobuf; messageType=\"google.rpc.Status\""
+    );
+}
@@ -0,0 +1,190 @@
+#![cfg(feature = "proto3-json")]
+
+mod app;
+
+use app::{Proto3JsonMessage, get_request, post_request};
+use axum::body::to_bytes;
+use axum::http::StatusCode;
+use axum::response::Response;
+use prost::Message;
+
+async fn body_text(res: Response) -> (StatusCode, String) {
+    let status = res.status();
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    dbg!(&body);
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+async fn echo(input: &'static str) -> (StatusCode, String) {
+    let headers = [
+        ("Content-Type", "application/json"),
+        ("Accept", "application/json"),
+    ];
+    body_text(post_request("/proto3-json/echo", &headers, input).await).await
+}
+
+#[tokio::test]
+async fn proto3_json() {
+    for input in [
+        r#"{"bigNumber": "9007199254740993"}"#,
+        r#"{"bigNumber": 9007199254740993}"#,
+        r#"{"big_number": "9007199254740993"}"#,
+    ] {
+        println!("Trying {input}");
+        let res = echo(input).await;
+        assert_eq!(
+            res,
+            (
+                StatusCode::OK,
+                r#"{"bigNumber":"9007199254740993"}"#.to_owned()
+            )
+        );
+    }
+}
+
+#[tokio::test]
+async fn proto3_json_default_values() {
+    let res = echo(r#"{"bigNumber": "0", "status": "STATUS_UNSPECIFIED"}"#).await;
+    assert_eq!(res, (StatusCode::OK, "{}".to_owned()));
+}
+
+#[tokio::test]
+async fn proto3_json_enum() {
+    for input in [r#"{"status": "STATUS_ARCHIVED"}"#, r#"{"status": 2}"#] {
+        println!("Trying {input}");
+        let res = echo(input).await;
+        assert_eq!(
+            res,
+            (StatusCode::OK, r#"{"status":"STATUS_ARCHIVED"}"#.to_owned())
+        );
+    }
+
+    let res = echo(r#"{"status": "ARCHIVED"}"#).await;
+    assert_eq!(res.0, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn proto3_json_timestamp() {
+    for input in [
+        r#"{"createdAt": "2024-01-02T03:04:05Z"}"#,
+        r#"{"createdAt": "2024-01-02T04:04:05+01:00"}"#,
+    ] {
+        println!("Trying {input}");
+        let res = echo(input).await;
+        assert_eq!(
+            res,
+            (
+                StatusCode::OK,
+                r#"{"createdAt":"2024-01-02T03:04:05Z"}"#.to_owned()
+            )
+        );
+    }
+
+    let res = echo(r#"{"createdAt": "2024-01-02T03:04:05.500Z"}"#).await;
+    assert_eq!(
+        res,
+        (
+            StatusCode::OK,
+            r#"{"createdAt":"2024-01-02T03:04:05.500Z"}"#.to_owned()
+        )
+    );
+
+    let res = echo(r#"{"createdAt": 1704164645}"#).await;
+    assert_eq!(res.0, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn proto3_json_wrappers() {
+    let res = echo(r#"{"nickname": "bob", "retries": 3}"#).await;
+    assert_eq!(
+        res,
+        (
+            StatusCode::OK,
+            r#"{"nickname":"bob","retries":3}"#.to_owned()
+        )
+    );
+
+    // Unlike scalar fields, wrappers keep their default values
+    let res = echo(r#"{"nickname": "", "retries": 0}"#).await;
+    assert_eq!(
+        res,
+        (StatusCode::OK, r#"{"nickname":"","retries":0}"#.to_owned())
+    );
+
+    let res = echo(r#"{"nickname": null}"#).await;
+    assert_eq!(res, (StatusCode::OK, "{}".to_owned()));
+}
+
+#[tokio::test]
+async fn proto3_json_protobuf() {
+    let headers = [
+        ("Content-Type", "application/json"),
+        ("Accept", "application/protobuf"),
+    ];
+    let input =
+        r#"{"status": "STATUS_ACTIVE", "createdAt": "2024-01-02T03:04:05Z", "nickname": "bob"}"#;
+    let res = post_request("/proto3-json/echo", &headers, input).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    let message = Proto3JsonMessage::decode(body).unwrap();
+    assert_eq!(message.status, 1);
+    assert_eq!(message.created_at.unwrap().seconds, 1704164645);
+    assert_eq!(message.nickname.as_deref(), Some("bob"));
+    assert_eq!(message.retries, None);
+}
+
+#[tokio::test]
+async fn proto3_json_invalid() {
+    let res = echo(r#"{"bigNumber": "#).await;
+    assert_eq!(res.0, StatusCode::BAD_REQUEST);
+    assert!(
+        res.1
+            .starts_with("Failed to parse the request body as JSON: ")
+    );
+
+    for input in [r#"{"unknown": 1}"#, r#"{"bigNumber": "abc"}"#] {
+        println!("Trying {input}");
+        let res = echo(input).await;
+        assert_eq!(res.0, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(
+            res.1
+                .starts_with("Failed to deserialize the JSON body into the target type: ")
+        );
+    }
+}
+
+#[tokio::test]
+async fn proto3_json_negotiate_and_encoded() {
+    for uri in ["/proto3-json/negotiate", "/proto3-json/encoded"] {
+        println!("Trying {uri}");
+        let res = get_request(uri, &[("Accept", "application/json")]).await;
+        assert_eq!(
+            body_text(res).await,
+            (
+                StatusCode::OK,
+                r#"{"bigNumber":"9007199254740993","status":"STATUS_ACTIVE","createdAt":"2024-01-02T03:04:05Z","nickname":""}"#.to_owned()
+            )
+        );
+    }
+}
+
+#[tokio::test]
+async fn proto3_json_protojson_uses_serde() {
+    let headers = [
+        ("Content-Type", "application/json"),
+        ("Accept", "application/json"),
+    ];
+    let res = post_request(
+        "/protojson/echo",
+        &headers,
+        r#"{"big_number": 9007199254740993}"#,
+    )
+    .await;
+    assert_eq!(
+        body_text(res).await,
+        (
+            StatusCode::OK,
+            r#"{"big_number":9007199254740993}"#.to_owned()
+        )
+    );
+}
@@ -1,8 +1,14 @@
 mod app;
 
-use app::build_app;
+use std::convert::Infallible;
+
+use app::{build_app, get_request};
 use axum::body::{Body, to_bytes};
 use axum::http::{Request, StatusCode};
+use axum_protobuf::ProtobufRejection;
+use base64::Engine;
+use base64::engine::general_purpose::{URL_SAFE, URL_SAFE_NO_PAD};
+use futures_util::stream;
 use prost::Message;
 use tower::ServiceExt;
 
@@ -70,6 +76,81 @@ async fn protobuf_extractor_invalid_body() {
     assert_eq!(body.iter().as_slice(), b"Protobuf decoding error");
 }
 
+#[tokio::test]
+async fn protobuf_extractor_decode_diagnostics() {
+    let app = build_app();
+    let requests: [(&[u8], &str); 3] = [
+        (
+            b"\x0a\x01\xff",
+            "Protobuf decoding error\n\n\
+             field: test\n\
+             error: failed to decode Protobuf message: TestMessage.test: invalid string value: data is not UTF-8 encoded\n",
+        ),
+        (
+            b"\x08\x01",
+            "Protobuf decoding error\n\n\
+             field: test\n\
+             wire type: Varint (expected LengthDelimited)\n\
+             error: failed to decode Protobuf message: TestMessage.test: invalid wire type: Varint (expected LengthDelimited)\n",
+        ),
+        (
+            b"\x0a\x05ab",
+            "Protobuf decoding error\n\n\
+             field: test\n\
+             error: failed to decode Protobuf message: TestMessage.test: buffer underflow\n",
+        ),
+    ];
+    for (input, expected) in requests {
+        let res = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/protobuf/input/diagnostics")
+                    .header("Content-Type", "application/protobuf")
+                    .body(Body::from(input))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(res.headers()["content-type"], "text/plain; charset=utf-8");
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        dbg!(&body);
+        assert_eq!(std::str::from_utf8(&body).unwrap(), expected);
+    }
+
+    // Other rejections and the default configuration are unchanged
+    let res = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/protobuf/input/diagnostics")
+                .header("Content-Type", "text/plain")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    assert!(!res.headers().contains_key("content-type"));
+    let res = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/protobuf/input")
+                .header("Content-Type", "application/protobuf")
+                .body(Body::from(b"\x08\x01".as_slice()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(body.iter().as_slice(), b"Protobuf decoding error");
+}
+
 #[tokio::test]
 async fn protobuf_extractor_simple() {
     let app = build_app();
@@ -97,6 +178,37 @@ async fn protobuf_extractor_simple() {
     assert_eq!(body.iter().as_slice(), test_string.as_bytes());
 }
 
+#[tokio::test]
+async fn protobuf_extractor_chunked_body() {
+    let app = build_app();
+    let test_string = "test";
+    let mut input = Vec::new();
+    TestMessage {
+        test: test_string.to_owned(),
+    }
+    .encode(&mut input)
+    .unwrap();
+    let chunks = input
+        .chunks(1)
+        .map(|chunk| Ok::<_, Infallible>(chunk.to_vec()))
+        .collect::<Vec<_>>();
+    let res = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/protobuf/input")
+                .header("Content-Type", "application/protobuf")
+                .body(Body::from_stream(stream::iter(chunks)))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    dbg!(&body);
+    assert_eq!(body.iter().as_slice(), test_string.as_bytes());
+}
+
 #[tokio::test]
 async fn protobuf_extractor_alternative_content_types() {
     let app = build_app();
@@ -111,6 +223,9 @@ async fn protobuf_extractor_alternative_content_types() {
         "application/protobuf",
         "application/x-protobuf",
         "application/vnd.google.protobuf",
+        "Application/Protobuf",
+        "application/x-protobuf; messageType=\"foo.Bar\"",
+        "application/vnd.foo+proto",
     ];
 
     for ct in content_types {
@@ -134,6 +249,612 @@ async fn protobuf_extractor_alternative_content_types() {
     }
 }
 
+#[tokio::test]
+async fn protobuf_extractor_invalid_content_types() {
+    let app = build_app();
+    let content_types = [
+        "application/json",
+        "application/protobuf-foo",
+        "text/protobuf",
+        "application/protobuf; foo",
+    ];
+
+    for ct in content_types {
+        println!("Trying Content-Type: {ct}");
+        let res = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/protobuf/input")
+                    .header("Content-Type", ct)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+}
+
+#[tokio::test]
+async fn protobuf_extractor_content_type_params() {
+    let app = build_app();
+    let res = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/protobuf/input/content-type")
+                .header(
+                    "Content-Type",
+                    "application/x-protobuf; charset=utf-8; MessageType=\"foo.Bar\"",
+                )
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    dbg!(&body);
+    assert_eq!(body.iter().as_slice(), b"foo.Bar");
+}
+
+#[tokio::test]
+async fn protobuf_extractor_optional() {
+    let app = build_app();
+    let mut input = Vec::new();
+    TestMessage {
+        test: "test".to_owned(),
+    }
+    .encode(&mut input)
+    .unwrap();
+    let requests = [
+        (None, Vec::new(), StatusCode::OK, "none"),
+        (
+            Some("application/protobuf"),
+            Vec::new(),
+            StatusCode::OK,
+            "none",
+        ),
+        (Some("application/protobuf"), input, StatusCode::OK, "test"),
+        (
+            Some("application/protobuf"),
+            b"invalid".to_vec(),
+            StatusCode::BAD_REQUEST,
+            "Protobuf decoding error",
+        ),
+        (
+            Some("application/json"),
+            b"{}".to_vec(),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "Missing 'content-type: application/protobuf' header",
+        ),
+    ];
+
+    for (ct, input, status, expected) in requests {
+        println!("Trying Content-Type: {ct:?} with body {input:?}");
+        let mut req = Request::builder()
+            .method("POST")
+            .uri("/protobuf/input/optional");
+        if let Some(ct) = ct {
+            req = req.header("Content-Type", ct);
+        }
+        let res = app
+            .clone()
+            .oneshot(req.body(Body::from(input)).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), status);
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        dbg!(&body);
+        assert_eq!(body.iter().as_slice(), expected.as_bytes());
+    }
+}
+
+#[tokio::test]
+async fn protobuf_extractor_default_body_limit() {
+    let app = build_app();
+    let mut input = Vec::new();
+    TestMessage {
+        test: "this is too long".to_owned(),
+    }
+    .encode(&mut input)
+    .unwrap();
+    let res = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/protobuf/input/small")
+                .header("Content-Type", "application/protobuf")
+                .body(Body::from(input))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    dbg!(&body);
+    assert_eq!(body.iter().as_slice(), b"Request body is too large");
+}
+
+#[tokio::test]
+async fn protobuf_extractor_default_body_limit_content_length() {
+    let app = build_app();
+    let res = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/protobuf/input/small")
+                .header("Content-Type", "application/protobuf")
+                .header("Content-Length", "1000000")
+                .body(Body::from(b"\x0a\x01a".as_slice()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[tokio::test]
+async fn protobuf_extractor_limited() {
+    let app = build_app();
+    let inputs = [
+        ("test", StatusCode::OK),
+        ("too long", StatusCode::PAYLOAD_TOO_LARGE),
+    ];
+
+    for (test_string, status) in inputs {
+        println!("Trying message: {test_string}");
+        let mut input = Vec::new();
+        TestMessage {
+            test: test_string.to_owned(),
+        }
+        .encode(&mut input)
+        .unwrap();
+        let res = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/protobuf/input/limited")
+                    .header("Content-Type", "application/protobuf")
+                    .body(Body::from(input))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), status);
+    }
+}
+
+#[tokio::test]
+async fn protobuf_extractor_limited_content_length() {
+    let app = build_app();
+    let res = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/protobuf/input/limited")
+                .header("Content-Type", "application/protobuf")
+                .header("Content-Length", "1000000")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[tokio::test]
+async fn protobuf_named() {
+    for (content_type, uri, expected_content_type) in [
+        (
+            "application/protobuf",
+            "/protobuf/named",
+            "application/protobuf; messageType=\"test.TestMessage\"",
+        ),
+        (
+            "application/protobuf; messageType=\"test.TestMessage\"",
+            "/protobuf/named",
+            "application/protobuf; messageType=\"test.TestMessage\"",
+        ),
+        (
+            "application/x-protobuf; messagetype=.test.TestMessage",
+            "/protobuf/named",
+            "application/protobuf; messageType=\"test.TestMessage\"",
+        ),
+        (
+            "application/protobuf",
+            "/protobuf/named/config",
+            "application/x-protobuf; messageType=\"test.TestMessage\"",
+        ),
+    ] {
+        println!("Trying {content_type} on {uri}");
+        let app = build_app();
+        let res = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(uri)
+                    .header("Content-Type", content_type)
+                    .body(Body::from(
+                        TestMessage {
+                            test: "test".to_owned(),
+                        }
+                        .encode_to_vec(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers().get("Content-Type").unwrap(),
+            expected_content_type
+        );
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        dbg!(&body);
+        let message = TestMessage::decode(body).unwrap();
+        assert_eq!(message.test, "test");
+    }
+}
+
+#[tokio::test]
+async fn protobuf_named_mismatch() {
+    for content_type in [
+        "application/protobuf; messageType=\"test.OtherMessage\"",
+        "application/protobuf; messageType=TestMessage",
+    ] {
+        println!("Trying {content_type}");
+        let app = build_app();
+        let res = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/protobuf/named")
+                    .header("Content-Type", content_type)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        dbg!(&body);
+        assert_eq!(
+            body,
+            "Unexpected 'messageType' parameter in 'content-type' header"
+        );
+    }
+
+    // The content type is checked first
+    let app = build_app();
+    let res = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/protobuf/named")
+                .header("Content-Type", "application/json; messageType=x")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(body, "Missing 'content-type: application/protobuf' header");
+}
+
+#[tokio::test]
+async fn protobuf_extractor_config() {
+    let app = build_app();
+    let mut input = Vec::new();
+    TestMessage {
+        test: "test".to_owned(),
+    }
+    .encode(&mut input)
+    .unwrap();
+    let mut long_input = Vec::new();
+    TestMessage {
+        test: "too long".to_owned(),
+    }
+    .encode(&mut long_input)
+    .unwrap();
+    let requests = [
+        (
+            "application/octet-stream",
+            input.clone(),
+            StatusCode::OK,
+            "test",
+        ),
+        (
+            "application/protobuf",
+            input,
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "Missing 'content-type: application/protobuf' header",
+        ),
+        (
+            "application/octet-stream",
+            long_input,
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "Request body is too large",
+        ),
+        (
+            "application/octet-stream",
+            Vec::new(),
+            StatusCode::BAD_REQUEST,
+            "Request body is empty",
+        ),
+    ];
+
+    for (ct, input, status, expected) in requests {
+        println!("Trying Content-Type: {ct} with body {input:?}");
+        let res = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/protobuf/input/config")
+                    .header("Content-Type", ct)
+                    .body(Body::from(input))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), status);
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        dbg!(&body);
+        assert_eq!(body.iter().as_slice(), expected.as_bytes());
+    }
+}
+
+#[tokio::test]
+async fn protobuf_extractor_config_extension() {
+    let app = build_app();
+    let res = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/protobuf/input/config-extension")
+                .header("Content-Type", "application/protobuf")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    dbg!(&body);
+    assert_eq!(body.iter().as_slice(), b"Request body is empty");
+}
+
+fn length_delimited_messages(tests: &[&str]) -> Vec<u8> {
+    let mut input = Vec::new();
+    for test in tests {
+        TestMessage {
+            test: (*test).to_owned(),
+        }
+        .encode_length_delimited(&mut input)
+        .unwrap();
+    }
+    input
+}
+
+#[tokio::test]
+async fn protobuf_stream() {
+    let app = build_app();
+    let tests = ["a", "", "test", &"long".repeat(100)];
+    let input = length_delimited_messages(&tests);
+
+    for chunk_size in [1, 3, 1000] {
+        println!("Trying chunk size: {chunk_size}");
+        let chunks = input
+            .chunks(chunk_size)
+            .map(|chunk| Ok::<_, Infallible>(chunk.to_vec()))
+            .collect::<Vec<_>>();
+        let res = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/protobuf/stream")
+                    .header("Content-Type", "application/protobuf")
+                    .body(Body::from_stream(stream::iter(chunks)))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        dbg!(&body);
+        assert_eq!(body.iter().as_slice(), tests.join(",").as_bytes());
+    }
+}
+
+#[tokio::test]
+async fn protobuf_stream_errors() {
+    let app = build_app();
+    let mut truncated = length_delimited_messages(&["test"]);
+    truncated.pop();
+    let requests = [
+        (
+            "/protobuf/stream",
+            Some("application/protobuf"),
+            Vec::new(),
+            StatusCode::OK,
+            "",
+        ),
+        (
+            "/protobuf/stream",
+            None,
+            Vec::new(),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "Missing 'content-type: application/protobuf' header",
+        ),
+        (
+            "/protobuf/stream",
+            Some("application/protobuf"),
+            truncated,
+            StatusCode::BAD_REQUEST,
+            "Protobuf decoding error",
+        ),
+        (
+            "/protobuf/stream",
+            Some("application/protobuf"),
+            vec![0xff; 10],
+            StatusCode::BAD_REQUEST,
+            "Protobuf decoding error",
+        ),
+        (
+            "/protobuf/stream/config",
+            Some("application/protobuf"),
+            length_delimited_messages(&["test", "too long"]),
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "Request body is too large",
+        ),
+    ];
+
+    for (uri, ct, input, status, expected) in requests {
+        println!("Trying {uri} with Content-Type: {ct:?} and body {input:?}");
+        let mut req = Request::builder().method("POST").uri(uri);
+        if let Some(ct) = ct {
+            req = req.header("Content-Type", ct);
+        }
+        let res = app
+            .clone()
+            .oneshot(req.body(Body::from(input)).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), status);
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        dbg!(&body);
+        assert_eq!(body.iter().as_slice(), expected.as_bytes());
+    }
+}
+
+#[tokio::test]
+async fn protobuf_stream_response() {
+    let app = build_app();
+    let res = app
+        .oneshot(
+            Request::builder()
+                .method("GET")
+                .uri("/protobuf/stream/output")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        res.headers().get("Content-Type").unwrap(),
+        "application/protobuf"
+    );
+    let mut body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    dbg!(&body);
+    let mut tests = Vec::new();
+    while !body.is_empty() {
+        tests.push(
+            TestMessage::decode_length_delimited(&mut body)
+                .unwrap()
+                .test,
+        );
+    }
+    assert_eq!(tests, ["a", "b", "c"]);
+}
+
+#[tokio::test]
+async fn protobuf_stream_response_error() {
+    let app = build_app();
+    let res = app
+        .oneshot(
+            Request::builder()
+                .method("GET")
+                .uri("/protobuf/stream/output/error")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = to_bytes(res.into_body(), usize::MAX).await;
+    dbg!(&body);
+    assert!(body.is_err());
+}
+
+#[tokio::test]
+async fn protobuf_query() {
+    let app = build_app();
+    let mut input = Vec::new();
+    TestMessage {
+        test: "test?".to_owned(),
+    }
+    .encode(&mut input)
+    .unwrap();
+    let no_pad = URL_SAFE_NO_PAD.encode(&input);
+    let pad = URL_SAFE.encode(&input).replace('=', "%3D");
+    let uris = [
+        (
+            format!("/protobuf/query?message={no_pad}"),
+            StatusCode::OK,
+            "test?",
+        ),
+        (
+            format!("/protobuf/query?a=b&message={pad}"),
+            StatusCode::OK,
+            "test?",
+        ),
+        (
+            format!("/protobuf/query/custom?q={no_pad}"),
+            StatusCode::OK,
+            "test?",
+        ),
+        (
+            format!("/protobuf/query/custom?message={no_pad}"),
+            StatusCode::BAD_REQUEST,
+            "Missing query parameter containing the protobuf message",
+        ),
+        (
+            "/protobuf/query".to_owned(),
+            StatusCode::BAD_REQUEST,
+            "Missing query parameter containing the protobuf message",
+        ),
+        (
+            "/protobuf/query?message=a$b".to_owned(),
+            StatusCode::BAD_REQUEST,
+            "Query parameter containing the protobuf message is not valid base64url",
+        ),
+        (
+            format!(
+                "/protobuf/query?message={}",
+                URL_SAFE_NO_PAD.encode("invalid")
+            ),
+            StatusCode::BAD_REQUEST,
+            "Protobuf decoding error",
+        ),
+    ];
+
+    for (uri, status, expected) in uris {
+        println!("Trying URI: {uri}");
+        let res = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri(uri)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), status);
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        dbg!(&body);
+        assert_eq!(body.iter().as_slice(), expected.as_bytes());
+    }
+}
+
 #[tokio::test]
 async fn protobuf_response() {
     let app = build_app();
@@ -152,8 +873,144 @@ async fn protobuf_response() {
         res.headers().get("Content-Type").unwrap(),
         "application/protobuf"
     );
+    assert_eq!(res.headers().get("Content-Length").unwrap(), "6");
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    dbg!(&body);
+    let message = TestMessage::decode(body).unwrap();
+    assert_eq!(message.test, "test");
+}
+
+#[tokio::test]
+async fn protobuf_response_boxed() {
+    let app = build_app();
+    let res = app
+        .oneshot(
+            Request::builder()
+                .method("GET")
+                .uri("/protobuf/output/boxed")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers().get("Content-Length").unwrap(), "6");
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    dbg!(&body);
+    let message = TestMessage::decode(body).unwrap();
+    assert_eq!(message.test, "test");
+}
+
+#[tokio::test]
+async fn protobuf_response_shared() {
+    for uri in ["/protobuf/output/shared", "/protobuf/output/shared/static"] {
+        println!("Trying URI: {uri}");
+        let app = build_app();
+        let res = app
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri(uri)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers().get("Content-Type").unwrap(),
+            "application/protobuf"
+        );
+        assert_eq!(res.headers().get("Content-Length").unwrap(), "6");
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        dbg!(&body);
+        let message = TestMessage::decode(body).unwrap();
+        assert_eq!(message.test, "test");
+    }
+}
+
+#[tokio::test]
+async fn protobuf_response_encoded() {
+    let app = build_app();
+    let res = app
+        .oneshot(
+            Request::builder()
+                .method("GET")
+                .uri("/protobuf/output/encoded")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        res.headers().get("Content-Type").unwrap(),
+        "application/protobuf"
+    );
+    assert_eq!(res.headers().get("Content-Length").unwrap(), "6");
     let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
     dbg!(&body);
     let message = TestMessage::decode(body).unwrap();
     assert_eq!(message.test, "test");
 }
+
+#[tokio::test]
+async fn protobuf_response_config() {
+    let app = build_app();
+    let res = app
+        .oneshot(
+            Request::builder()
+                .method("GET")
+                .uri("/protobuf/output/config")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        res.headers().get("Content-Type").unwrap(),
+        "application/x-protobuf"
+    );
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    dbg!(&body);
+    let message = TestMessage::decode(body).unwrap();
+    assert_eq!(message.test, "test");
+}
+
+#[tokio::test]
+async fn protobuf_response_handler_content_type() {
+    let res = get_request("/protobuf/output/content-type", &[]).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        res.headers().get("Content-Type").unwrap(),
+        "application/vnd.acme+proto"
+    );
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    dbg!(&body);
+    let message = TestMessage::decode(body).unwrap();
+    assert_eq!(message.test, "test");
+}
+
+#[test]
+fn protobuf_rejection_error() {
+    use std::error::Error;
+
+    let e = TestMessage::decode(&b"\x0a\x05test"[..]).unwrap_err();
+    let rejection = ProtobufRejection::ProtobufDecodeError(e.clone());
+    assert_eq!(rejection.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(rejection.body_text(), "Protobuf decoding error");
+    assert_eq!(rejection.to_string(), "Protobuf decoding error");
+    assert_eq!(
+        rejection
+            .source()
+            .unwrap()
+            .downcast_ref::<prost::DecodeError>(),
+        Some(&e)
+    );
+
+    let rejection = ProtobufRejection::EmptyBody;
+    assert_eq!(rejection.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(rejection.body_text(), "Request body is empty");
+    assert!(rejection.source().is_none());
+}
@@ -1,13 +1,16 @@
 mod app;
 
-use app::build_app;
+use app::{build_app, get_request, post_request};
+use axum::Json;
 use axum::body::{Body, to_bytes};
+use axum::extract::rejection::JsonRejection;
 use axum::http::{Request, StatusCode};
+use axum_protobuf::{ProtoJsonRejection, ProtobufRejection};
 use prost::Message;
 use serde_json::{from_slice, json};
 use tower::ServiceExt;
 
-use crate::app::TestMessage;
+use crate::app::{Proto3Message, TestMessage};
 
 #[tokio::test]
 async fn protojson_extractor_no_content_type() {
@@ -94,6 +97,36 @@ async fn protojson_extractor_json_empty_body() {
     );
 }
 
+#[tokio::test]
+async fn protojson_extractor_json_body_limit() {
+    let res = post_request(
+        "/protojson/input/small",
+        &[("Content-Type", "application/json")],
+        r#"{"test":"too large"}"#,
+    )
+    .await;
+    // Same rejection as axum's `Json` extractor
+    assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    dbg!(&body);
+    assert_eq!(
+        body.iter().as_slice(),
+        b"Failed to buffer the request body: length limit exceeded"
+    );
+
+    // Larger than axum's default body limit
+    let test = "a".repeat(3_000_000);
+    let res = post_request(
+        "/protojson/input/unlimited",
+        &[("Content-Type", "application/json")],
+        json!({ "test": test }).to_string(),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(body.len(), test.len());
+}
+
 #[tokio::test]
 async fn protojson_extractor_protobuf_simple() {
     let app = build_app();
@@ -135,6 +168,9 @@ async fn protojson_extractor_protobuf_alternative_content_types() {
         "application/protobuf",
         "application/x-protobuf",
         "application/vnd.google.protobuf",
+        "Application/Protobuf",
+        "application/x-protobuf; messageType=\"foo.Bar\"",
+        "application/vnd.foo+proto",
     ];
 
     for ct in content_types {
@@ -180,6 +216,103 @@ async fn protojson_extractor_json_simple() {
     assert_eq!(body.iter().as_slice(), test_string.as_bytes());
 }
 
+#[tokio::test]
+async fn protojson_extractor_json_alternative_content_types() {
+    let app = build_app();
+    let test_string = "test";
+    let input = json!({ "test": test_string }).to_string();
+    let content_types = [
+        "application/json; charset=utf-8",
+        "Application/JSON",
+        "application/vnd.api+json",
+    ];
+
+    for ct in content_types {
+        println!("Trying Content-Type: {ct}");
+        let res = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/protojson/input")
+                    .header("Content-Type", ct)
+                    .body(Body::from(input.clone()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        dbg!(&body);
+        assert_eq!(body.iter().as_slice(), test_string.as_bytes());
+    }
+}
+
+#[tokio::test]
+async fn protojson_extractor_optional() {
+    let app = build_app();
+    let mut input = Vec::new();
+    TestMessage {
+        test: "test".to_owned(),
+    }
+    .encode(&mut input)
+    .unwrap();
+    let requests = [
+        (None, Vec::new(), StatusCode::OK, "none"),
+        (Some("application/json"), Vec::new(), StatusCode::OK, "none"),
+        (
+            Some("application/protobuf"),
+            Vec::new(),
+            StatusCode::OK,
+            "none",
+        ),
+        (
+            Some("application/json"),
+            json!({ "test": "test" }).to_string().into_bytes(),
+            StatusCode::OK,
+            "test",
+        ),
+        (Some("application/protobuf"), input, StatusCode::OK, "test"),
+        (
+            Some("application/json"),
+            b"invalid".to_vec(),
+            StatusCode::BAD_REQUEST,
+            "Failed to parse the request body as JSON: expected value at line 1 column 1",
+        ),
+        (
+            Some("application/protobuf"),
+            b"invalid".to_vec(),
+            StatusCode::BAD_REQUEST,
+            "Protobuf decoding error",
+        ),
+        (
+            Some("text/plain"),
+            b"test".to_vec(),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "Missing 'content-type' header that has the value 'application/json' or 'application/protobuf'",
+        ),
+    ];
+
+    for (ct, input, status, expected) in requests {
+        println!("Trying Content-Type: {ct:?} with body {input:?}");
+        let mut req = Request::builder()
+            .method("POST")
+            .uri("/protojson/input/optional");
+        if let Some(ct) = ct {
+            req = req.header("Content-Type", ct);
+        }
+        let res = app
+            .clone()
+            .oneshot(req.body(Body::from(input)).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), status);
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        dbg!(&body);
+        assert_eq!(body.iter().as_slice(), expected.as_bytes());
+    }
+}
+
 #[tokio::test]
 async fn protojson_response_no_accept() {
     let app = build_app();
@@ -251,3 +384,423 @@ async fn protojson_response_json() {
     let message = from_slice::<TestMessage>(body.iter().as_slice()).unwrap();
     assert_eq!(message.test, "test");
 }
+
+#[tokio::test]
+async fn protojson_response_encoded() {
+    for accept in ["application/protobuf", "application/json"] {
+        println!("Trying {accept}");
+        let app = build_app();
+        let res = app
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri("/protojson/output/encoded")
+                    .header("Accept", accept)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get("Content-Type").unwrap(), accept);
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        dbg!(&body);
+        let message = if accept == "application/json" {
+            from_slice::<TestMessage>(body.iter().as_slice()).unwrap()
+        } else {
+            TestMessage::decode(body).unwrap()
+        };
+        assert_eq!(message.test, "test");
+    }
+}
+
+#[tokio::test]
+async fn protojson_negotiate() {
+    for accept in ["application/protobuf", "application/json"] {
+        println!("Trying {accept}");
+        let app = build_app();
+        let res = app
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri("/protojson/negotiate")
+                    .header("Accept", accept)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get("Content-Type").unwrap(), accept);
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        dbg!(&body);
+        let message = if accept == "application/json" {
+            from_slice::<TestMessage>(body.iter().as_slice()).unwrap()
+        } else {
+            TestMessage::decode(body).unwrap()
+        };
+        assert_eq!(message.test, "test");
+    }
+}
+
+#[tokio::test]
+async fn protojson_negotiate_no_accept() {
+    let app = build_app();
+    let res = app
+        .oneshot(
+            Request::builder()
+                .method("GET")
+                .uri("/protojson/negotiate")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    dbg!(&body);
+    assert_eq!(
+        body,
+        "Missing 'accept' header with value 'application/json' or 'application/protobuf'"
+    );
+}
+
+#[tokio::test]
+async fn protojson_negotiate_keeps_response_parts() {
+    let res = get_request(
+        "/protojson/negotiate/created",
+        &[("Accept", "application/json")],
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    assert_eq!(res.headers().get("Location").unwrap(), "/test");
+    assert_eq!(
+        res.headers().get("Content-Type").unwrap(),
+        "application/json"
+    );
+    let vary: Vec<_> = res.headers().get_all("Vary").iter().collect();
+    // Followed by `accept-encoding` with a compression feature
+    assert_eq!(vary[..2], ["origin", "accept"]);
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(body, r#"{"test":"test"}"#);
+
+    let res = get_request("/protojson/negotiate/created", &[("Accept", "text/html")]).await;
+    assert_eq!(res.status(), StatusCode::NOT_ACCEPTABLE);
+    assert_eq!(res.headers().get("Location"), None);
+}
+
+#[tokio::test]
+async fn protojson_negotiate_no_layer() {
+    let app = build_app();
+    let res = app
+        .oneshot(
+            Request::builder()
+                .method("GET")
+                .uri("/protojson/negotiate/no-layer")
+                .header("Accept", "application/json")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        res.headers().get("Content-Type").unwrap(),
+        "application/protobuf"
+    );
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    let message = TestMessage::decode(body).unwrap();
+    assert_eq!(message.test, "test");
+}
+
+#[tokio::test]
+async fn protojson_negotiate_error_status() {
+    let res = get_request(
+        "/protojson/negotiate/error",
+        &[("Accept", "application/json")],
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(
+        res.headers().get("Content-Type").unwrap(),
+        "application/json"
+    );
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(body, r#"{"test":"error"}"#);
+}
+
+#[tokio::test]
+async fn protojson_response_negotiation() {
+    for (accept, content_type) in [
+        ("application/json, text/plain;q=0.5", "application/json"),
+        ("*/*", "application/json"),
+        ("application/*", "application/json"),
+        (
+            "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
+            "application/json",
+        ),
+        (
+            "application/json;q=0.5, application/protobuf",
+            "application/protobuf",
+        ),
+        ("application/json;q=0, */*", "application/protobuf"),
+        (
+            "application/*;q=0.5, application/json;q=0",
+            "application/protobuf",
+        ),
+        ("Application/X-Protobuf", "application/protobuf"),
+        // Invalid q-values are ignored
+        (
+            "application/json;q=5, application/protobuf;q=0.5",
+            "application/protobuf",
+        ),
+        (
+            "application/json;q=inf, application/json;q=1e9, application/protobuf;q=0.001",
+            "application/protobuf",
+        ),
+        (
+            "application/protobuf;q=1.5, application/json;q=0.0001, */*;q=0.1",
+            "application/json",
+        ),
+    ] {
+        println!("Trying {accept}");
+        let app = build_app();
+        let res = app
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri("/protojson/output")
+                    .header("Accept", accept)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get("Content-Type").unwrap(), content_type);
+        assert_eq!(res.headers().get("Vary").unwrap(), "accept");
+    }
+}
+
+#[tokio::test]
+async fn protojson_response_not_acceptable() {
+    for accept in [
+        "text/plain",
+        "application/json;q=0",
+        "image/*, application/xml",
+    ] {
+        println!("Trying {accept}");
+        let app = build_app();
+        let res = app
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri("/protojson/output")
+                    .header("Accept", accept)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_ACCEPTABLE);
+        assert_eq!(res.headers().get("Vary").unwrap(), "accept");
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        dbg!(&body);
+        assert_eq!(
+            body,
+            "None of the media types of the 'accept' header are available, use 'application/json' or 'application/protobuf'"
+        );
+    }
+}
+
+#[tokio::test]
+async fn protojson_negotiate_fallback_request_unchanged() {
+    let res = get_request(
+        "/protojson/negotiate/fallback/protobuf",
+        &[("Content-Type", "application/json")],
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        res.headers().get("Content-Type").unwrap(),
+        "application/protobuf"
+    );
+    // The handler doesn't see an `accept` header that the client didn't send
+    assert_eq!(res.headers().get("x-request-accept").unwrap(), "none");
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    dbg!(&body);
+    let message = TestMessage::decode(body).unwrap();
+    assert_eq!(message.test, "test");
+}
+
+#[tokio::test]
+async fn protojson_negotiate_fallback() {
+    for (accept, content_type) in [
+        (None, "application/json"),
+        (Some("application/protobuf"), "application/protobuf"),
+    ] {
+        println!("Trying {accept:?}");
+        let app = build_app();
+        let mut req = Request::builder()
+            .method("GET")
+            .uri("/protojson/negotiate/fallback");
+        if let Some(accept) = accept {
+            req = req.header("Accept", accept);
+        }
+        let res = app.oneshot(req.body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get("Content-Type").unwrap(), content_type);
+    }
+}
+
+#[tokio::test]
+async fn protojson_response_fallback_same_as_request() {
+    let protobuf = Body::from(Proto3Message { big_number: 5 }.encode_to_vec());
+    for (content_type, accept, body, response_content_type) in [
+        (
+            "application/x-protobuf",
+            None,
+            protobuf,
+            "application/protobuf",
+        ),
+        (
+            "application/json",
+            None,
+            Body::from(r#"{"big_number": 5}"#),
+            "application/json",
+        ),
+        (
+            "application/json",
+            Some("application/protobuf"),
+            Body::from(r#"{"big_number": 5}"#),
+            "application/protobuf",
+        ),
+    ] {
+        println!("Trying {content_type} with {accept:?}");
+        let app = build_app();
+        let mut req = Request::builder()
+            .method("POST")
+            .uri("/protojson/echo/fallback")
+            .header("Content-Type", content_type);
+        if let Some(accept) = accept {
+            req = req.header("Accept", accept);
+        }
+        let res = app.oneshot(req.body(body).unwrap()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers().get("Content-Type").unwrap(),
+            response_content_type
+        );
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        dbg!(&body);
+        if response_content_type == "application/protobuf" {
+            assert_eq!(Proto3Message::decode(body).unwrap().big_number, 5);
+        }
+    }
+}
+
+#[tokio::test]
+async fn protojson_wire_format() {
+    let protobuf = TestMessage {
+        test: "test".to_owned(),
+    }
+    .encode_to_vec();
+    for (content_type, body, expected) in [
+        (
+            "application/json",
+            Body::from(r#"{"test": "test"}"#),
+            "Some(Json) test",
+        ),
+        (
+            "application/vnd.api+json",
+            Body::from(r#"{"test": "test"}"#),
+            "Some(Json) test",
+        ),
+        (
+            "application/x-protobuf",
+            Body::from(protobuf),
+            "Some(Protobuf) test",
+        ),
+    ] {
+        println!("Trying {content_type}");
+        let app = build_app();
+        let res = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/protojson/format")
+                    .header("Content-Type", content_type)
+                    .body(body)
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        dbg!(&body);
+        assert_eq!(body, expected);
+    }
+}
+
+#[tokio::test]
+async fn protojson_response_mirrors_request() {
+    for accept in [
+        None,
+        Some("*/*"),
+        Some("application/json, application/protobuf"),
+    ] {
+        println!("Trying {accept:?}");
+        let app = build_app();
+        let mut req = Request::builder()
+            .method("POST")
+            .uri("/protojson/echo")
+            .header("Content-Type", "application/protobuf");
+        if let Some(accept) = accept {
+            req = req.header("Accept", accept);
+        }
+        let body = Body::from(Proto3Message { big_number: 5 }.encode_to_vec());
+        let res = app.oneshot(req.body(body).unwrap()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers().get("Content-Type").unwrap(),
+            "application/protobuf"
+        );
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        dbg!(&body);
+        assert_eq!(Proto3Message::decode(body).unwrap().big_number, 5);
+    }
+}
+
+#[test]
+fn protojson_rejection_error() {
+    use std::error::Error;
+
+    let rejection = ProtoJsonRejection::ProtobufRejection(ProtobufRejection::PayloadTooLarge);
+    assert_eq!(rejection.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(rejection.body_text(), "Request body is too large");
+    assert_eq!(rejection.to_string(), "Request body is too large");
+    assert!(
+        rejection
+            .source()
+            .unwrap()
+            .downcast_ref::<ProtobufRejection>()
+            .is_some()
+    );
+
+    let json_rejection = Json::<TestMessage>::from_bytes(b"{").unwrap_err();
+    let body_text = json_rejection.body_text();
+    let rejection = ProtoJsonRejection::JsonRejection(json_rejection);
+    assert_eq!(rejection.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(rejection.body_text(), body_text);
+    assert!(
+        rejection
+            .source()
+            .unwrap()
+            .downcast_ref::<JsonRejection>()
+            .is_some()
+    );
+
+    let rejection = ProtoJsonRejection::MissingContentType;
+    assert_eq!(rejection.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    assert!(rejection.source().is_none());
+}
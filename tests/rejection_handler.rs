@@ -0,0 +1,84 @@
+mod app;
+
+use app::{get_request, post_request};
+use axum::body::to_bytes;
+use axum::http::StatusCode;
+use serde_json::{Value, json};
+
+async fn send(uri: &str, content_type: &str, body: &'static [u8]) -> (StatusCode, Value) {
+    let res = post_request(uri, &[("Content-Type", content_type)], body).await;
+    let status = res.status();
+    assert_eq!(
+        res.headers().get("Content-Type").unwrap(),
+        "application/json"
+    );
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    dbg!(&body);
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+#[tokio::test]
+async fn rejection_handler_protobuf() {
+    let res = send(
+        "/protobuf/input/custom-rejection",
+        "application/protobuf",
+        b"\x0a\x01\xff",
+    )
+    .await;
+    assert_eq!(
+        res,
+        (
+            StatusCode::BAD_REQUEST,
+            json!({
+                "kind": "protobuf",
+                "status": 400,
+                "message": "Protobuf decoding error",
+                "path": "/protobuf/input/custom-rejection",
+            })
+        )
+    );
+}
+
+#[tokio::test]
+async fn rejection_handler_protojson() {
+    let res = send("/protojson/input/custom-rejection", "text/plain", b"").await;
+    assert_eq!(
+        res,
+        (
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            json!({
+                "kind": "protojson",
+                "status": 415,
+                "message": "Missing 'content-type' header that has the value 'application/json' or 'application/protobuf'",
+                "path": "/protojson/input/custom-rejection",
+            })
+        )
+    );
+
+    let res = send(
+        "/protojson/input/custom-rejection",
+        "application/protobuf",
+        b"\x0a\x01\xff",
+    )
+    .await;
+    assert_eq!(res.0, StatusCode::BAD_REQUEST);
+    assert_eq!(res.1["kind"], "protojson");
+    assert_eq!(res.1["message"], "Protobuf decoding error");
+}
+
+#[tokio::test]
+async fn rejection_handler_extension() {
+    let res = get_request("/protobuf/query/custom-rejection?message=%%%", &[]).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    dbg!(&body);
+    assert_eq!(
+        serde_json::from_slice::<Value>(&body).unwrap(),
+        json!({
+            "kind": "protobuf",
+            "status": 400,
+            "message": "Query parameter containing the protobuf message is not valid base64url",
+            "path": "/protobuf/query/custom-rejection",
+        })
+    );
+}
@@ -0,0 +1,152 @@
+mod app;
+
+use app::post_request;
+use app::rpc::{BadRequest, FieldViolation, Status};
+use axum::body::to_bytes;
+use axum::http::StatusCode;
+use prost::Message;
+
+#[tokio::test]
+async fn rpc_status_decode_error() {
+    let res = post_request(
+        "/protobuf/input/rpc-status",
+        &[("Content-Type", "application/protobuf")],
+        b"\x0a\x01\xff",
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    dbg!(&body);
+    let status = Status::decode(body).unwrap();
+    assert_eq!(status.code, 3);
+    assert_eq!(status.message, "Protobuf decoding error");
+    // The path of the field is only sent with decode diagnostics
+    assert_eq!(status.details.len(), 1);
+    let bad_request = BadRequest::decode(status.details[0].value.as_slice()).unwrap();
+    assert_eq!(
+        bad_request.field_violations,
+        [FieldViolation {
+            field: String::new(),
+            description: "invalid string value: data is not UTF-8 encoded".to_owned(),
+        }]
+    );
+}
+
+#[tokio::test]
+async fn rpc_status_decode_diagnostics() {
+    let res = post_request(
+        "/protobuf/input/rpc-status/diagnostics",
+        &[("Content-Type", "application/protobuf")],
+        b"\x0a\x01\xff",
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        res.headers().get("Content-Type").unwrap(),
+        "application/protobuf; messageType=\"google.rpc.Status\""
+    );
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    dbg!(&body);
+    let status = Status::decode(body).unwrap();
+    assert_eq!(status.code, 3);
+    assert_eq!(status.message, "Protobuf decoding error");
+    assert_eq!(status.details.len(), 1);
+    assert_eq!(
+        status.details[0].type_url,
+        "type.googleapis.com/google.rpc.BadRequest"
+    );
+    let bad_request = BadRequest::decode(status.details[0].value.as_slice()).unwrap();
+    assert_eq!(
+        bad_request.field_violations,
+        [FieldViolation {
+            field: "test".to_owned(),
+            description: "invalid string value: data is not UTF-8 encoded".to_owned(),
+        }]
+    );
+}
+
+#[tokio::test]
+async fn rpc_status_missing_content_type() {
+    let res = post_request("/protobuf/input/rpc-status", &[], b"").await;
+    assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    dbg!(&body);
+    let status = Status::decode(body).unwrap();
+    assert_eq!(status.code, 3);
+    assert_eq!(
+        status.message,
+        "Missing 'content-type: application/protobuf' header"
+    );
+    assert!(status.details.is_empty());
+}
+
+#[tokio::test]
+async fn rpc_status_disabled() {
+    let res = post_request(
+        "/protobuf/input",
+        &[("Content-Type", "application/protobuf")],
+        b"\x0a\x01\xff",
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(body, "Protobuf decoding error");
+}
+
+#[cfg(feature = "serde")]
+#[tokio::test]
+async fn rpc_status_json() {
+    let res = post_request(
+        "/protojson/input/rpc-status",
+        &[
+            ("Content-Type", "application/protobuf"),
+            ("Accept", "application/json"),
+        ],
+        b"\x0a\x01\xff",
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        res.headers().get("Content-Type").unwrap(),
+        "application/json"
+    );
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    dbg!(&body);
+    let status: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(
+        status,
+        serde_json::json!({
+            "code": 3,
+            "message": "Protobuf decoding error",
+            "details": [{
+                "@type": "type.googleapis.com/google.rpc.BadRequest",
+                "fieldViolations": [{
+                    "field": "test",
+                    "description": "invalid string value: data is not UTF-8 encoded",
+                }],
+            }],
+        })
+    );
+
+    let res = post_request(
+        "/protojson/input/rpc-status",
+        &[("Content-Type", "application/json")],
+        b"{",
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        res.headers().get("Content-Type").unwrap(),
+        "application/json"
+    );
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    dbg!(&body);
+    let status: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(status["code"], 3);
+    assert!(
+        status["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("Failed to parse the request body as JSON")
+    );
+}
@@ -0,0 +1,90 @@
+#![cfg(feature = "text-format")]
+
+mod app;
+
+use app::{get_request, post_request};
+use axum::body::to_bytes;
+use axum::http::StatusCode;
+
+async fn send(uri: &str, content_type: &str, input: &'static str) -> (StatusCode, String) {
+    let res = post_request(uri, &[("Content-Type", content_type)], input).await;
+    let status = res.status();
+    let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+    dbg!(&body);
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn text_format_extractor() {
+    for content_type in [
+        "text/x-protobuf",
+        "application/x-protobuf-text",
+        "Text/X-Protobuf; charset=utf-8",
+    ] {
+        println!("Trying {content_type}");
+        let res = send("/text-format/input", content_type, "test: \"hello\"").await;
+        assert_eq!(res, (StatusCode::OK, "hello".to_owned()));
+    }
+}
+
+#[tokio::test]
+async fn text_format_extractor_json_and_protobuf() {
+    let res = send(
+        "/text-format/input",
+        "application/json",
+        r#"{"test":"hello"}"#,
+    )
+    .await;
+    assert_eq!(res, (StatusCode::OK, "hello".to_owned()));
+
+    let res = send("/text-format/input", "application/protobuf", "\n\x05hello").await;
+    assert_eq!(res, (StatusCode::OK, "hello".to_owned()));
+}
+
+#[tokio::test]
+async fn text_format_extractor_empty_body() {
+    let res = send("/text-format/input", "text/x-protobuf", "").await;
+    assert_eq!(res, (StatusCode::OK, "".to_owned()));
+
+    let res = send("/text-format/input/optional", "text/x-protobuf", "").await;
+    assert_eq!(res, (StatusCode::OK, "none".to_owned()));
+}
+
+#[tokio::test]
+async fn text_format_extractor_invalid_body() {
+    for input in ["test: 1", "unknown: \"hello\"", "test: \"hello"] {
+        println!("Trying {input}");
+        let res = send("/text-format/input", "text/x-protobuf", input).await;
+        assert_eq!(
+            res,
+            (
+                StatusCode::BAD_REQUEST,
+                "Protobuf text format parsing error".to_owned()
+            )
+        );
+    }
+}
+
+#[tokio::test]
+async fn text_format_not_supported_by_protojson() {
+    let res = send("/protojson/input", "text/x-protobuf", "test: \"hello\"").await;
+    assert_eq!(res.0, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    let res = get_request("/protojson/output", &[("Accept", "text/x-protobuf")]).await;
+    assert_eq!(res.status(), StatusCode::NOT_ACCEPTABLE);
+}
+
+#[tokio::test]
+async fn text_format_response() {
+    for uri in ["/text-format/output", "/text-format/negotiate"] {
+        for accept in ["text/x-protobuf", "application/x-protobuf-text"] {
+            println!("Trying {uri} with {accept}");
+            let res = get_request(uri, &[("Accept", accept)]).await;
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(res.headers().get("Content-Type").unwrap(), accept);
+            let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+            dbg!(&body);
+            assert_eq!(body, "test: \"test\"\n");
+        }
+    }
+}